@@ -0,0 +1,306 @@
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use crate::configs::{PageMarginConfig, PrinterProfile};
+
+/// Config file looked up in the current directory, its values win over the user's.
+pub const PROJECT_CONFIG_FILE: &str = "doc-printable.toml";
+
+/// Settings from the config files, before the command line is applied.
+///
+/// Both files take any `PageMarginConfig` field at the top level, a `processing` list of `-I`
+/// operations, `[profiles.NAME]` tables with the same keys that `--profile NAME` lays over the
+/// top level, and `[printers.NAME]` tables with a `PrinterProfile` that `--printer NAME` selects:
+///
+/// ```toml
+/// bleed = 1.0
+/// processing = ["c2s", "contrast:30"]
+///
+/// [profiles.office-letter]
+/// page_width = 215.9
+/// page_height = 279.4
+/// margin_top = 12.7
+/// cut_guides = { kind = "crop-marks" }
+///
+/// [printers.front-desk]
+/// unprintable = { top = 4.2, right = 4.2, bottom = 6.0, left = 4.2 }
+/// calibration = { offset_x = 1.0, offset_y = -0.5, scale_x = 1.0047 }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FileSettings {
+    /// Layout values of both files with the chosen profile laid over them
+    values: Table,
+    printer: Option<PrinterProfile>,
+    /// Image processing operations as written on the command line
+    pub processing: Vec<String>,
+}
+
+impl FileSettings {
+    /// Reads `~/.config/doc-printable/config.toml` and then `doc-printable.toml` from the
+    /// current directory. Missing files are skipped, a named profile or printer must exist in
+    /// one of them.
+    pub fn load(profile: Option<&str>, printer: Option<&str>) -> Result<Self, String> {
+        let paths = [user_config_path(), Some(PathBuf::from(PROJECT_CONFIG_FILE))];
+        let mut tables = Vec::new();
+        for path in paths.into_iter().flatten() {
+            let source = match std::fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("{}: {}", path.display(), e)),
+            };
+            let table = source
+                .parse::<Table>()
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            tables.push(table);
+        }
+        Self::from_tables(tables, profile, printer)
+    }
+
+    /// Merges the tables in order, later ones winning key by key, then applies `profile` and
+    /// `printer`.
+    pub fn from_tables(
+        tables: Vec<Table>,
+        profile: Option<&str>,
+        printer: Option<&str>,
+    ) -> Result<Self, String> {
+        let mut merged = Table::new();
+        for table in tables {
+            merge_tables(&mut merged, table);
+        }
+
+        let profiles = take_named_tables(&mut merged, "profiles")?;
+        let printers = take_named_tables(&mut merged, "printers")?;
+        if let Some(name) = profile {
+            merge_tables(&mut merged, find_named(&profiles, "profile", name)?);
+        }
+        let printer = match printer {
+            Some(name) => Some(
+                Value::Table(find_named(&printers, "printer", name)?)
+                    .try_into::<PrinterProfile>()
+                    .map_err(|e| format!("Invalid printer `{}`: {}", name, e))?,
+            ),
+            None => None,
+        };
+
+        let processing = match merged.remove("processing") {
+            Some(value) => value
+                .try_into::<Vec<String>>()
+                .map_err(|e| format!("processing: {}", e))?,
+            None => Vec::new(),
+        };
+        let settings = Self {
+            values: merged,
+            printer,
+            processing,
+        };
+        // Bad values are reported on loading, whichever base they are later laid over
+        settings.config(PageMarginConfig::default())?;
+        Ok(settings)
+    }
+
+    /// Lays the configured values over `base`, the defaults or a preset, then the printer's.
+    /// Fields the files leave out keep the value of `base`.
+    pub fn config(&self, base: PageMarginConfig) -> Result<PageMarginConfig, String> {
+        let mut values = serde_json::to_value(base).map_err(|e| e.to_string())?;
+        let overlay = serde_json::to_value(&self.values).map_err(|e| e.to_string())?;
+        merge_values(&mut values, overlay);
+        let mut cfg = serde_json::from_value::<PageMarginConfig>(values)
+            .map_err(|e| format!("Invalid config: {}", e))?;
+        if let Some(printer) = self.printer {
+            cfg.unprintable = printer.unprintable;
+            cfg.calibration = printer.calibration;
+        }
+        Ok(cfg)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/doc-printable/config.toml`, falling back to `~/.config`.
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("doc-printable").join("config.toml"))
+}
+
+/// Removes a table of named tables such as `profiles` from `table`.
+fn take_named_tables(table: &mut Table, key: &str) -> Result<Table, String> {
+    match table.remove(key) {
+        Some(Value::Table(named)) => Ok(named),
+        Some(_) => Err(format!("`{}` must be a table of named tables.", key)),
+        None => Ok(Table::new()),
+    }
+}
+
+/// Looks up `name` among the named tables, listing the known names when it is missing.
+fn find_named(named: &Table, kind: &str, name: &str) -> Result<Table, String> {
+    match named.get(name) {
+        Some(Value::Table(values)) => Ok(values.clone()),
+        Some(_) => Err(format!("The {} `{}` must be a table.", kind, name)),
+        None => {
+            let known: Vec<&str> = named.keys().map(String::as_str).collect();
+            Err(format!(
+                "Unknown {} `{}`, the config files define: {}.",
+                kind,
+                name,
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            ))
+        }
+    }
+}
+
+/// Lays `overlay` over `base` like `merge_tables`, for configs turned into JSON values.
+fn merge_values(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_values(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Lays `overlay` over `base`, descending into tables present in both.
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use printpdf::{Mm, Pt};
+
+    use super::*;
+    use crate::configs::{CardProfile, CutGuideKind, PaperSize};
+
+    fn tables(sources: &[&str]) -> Vec<Table> {
+        sources
+            .iter()
+            .map(|source| source.parse::<Table>().unwrap())
+            .collect()
+    }
+
+    fn load(sources: &[&str], profile: Option<&str>, printer: Option<&str>) -> FileSettings {
+        FileSettings::from_tables(tables(sources), profile, printer).unwrap()
+    }
+
+    #[test]
+    fn later_files_win_key_by_key() {
+        let settings = load(
+            &[
+                "bleed = 1.0\nmargin_top = 5.0\ncut_guides = { kind = \"crop-marks\", color = [1.0, 0.0, 0.0] }",
+                "bleed = 2.0\ncut_guides = { color = [0.0, 0.0, 1.0] }",
+            ],
+            None,
+            None,
+        );
+        let cfg = settings.config(PageMarginConfig::default()).unwrap();
+        assert_eq!(cfg.bleed, Mm(2.0));
+        assert_eq!(cfg.margin_top, Mm(5.0));
+        assert_eq!(cfg.cut_guides.kind, CutGuideKind::CropMarks);
+        assert_eq!(cfg.cut_guides.color, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn profile_overlays_the_top_level() {
+        let sources = [
+            "margin_top = 5.0\ncut_guides = { width = 2.0 }\n[profiles.wide]\nmargin_left = 20.0",
+            "[profiles.wide]\nmargin_top = 7.0\ncut_guides = { dash = [4, 4] }",
+        ];
+        let cfg = load(&sources, Some("wide"), None)
+            .config(PageMarginConfig::default())
+            .unwrap();
+        assert_eq!(cfg.margin_top, Mm(7.0));
+        assert_eq!(cfg.margin_left, Mm(20.0));
+        assert_eq!(cfg.cut_guides.width, Some(Pt(2.0)));
+        assert_eq!(cfg.cut_guides.dash, Some((4, 4)));
+
+        // Without the profile its tables stay out of the config
+        let cfg = load(&sources, None, None)
+            .config(PageMarginConfig::default())
+            .unwrap();
+        assert_eq!(cfg.margin_top, Mm(5.0));
+        assert_eq!(cfg.margin_left, PageMarginConfig::default().margin_left);
+    }
+
+    #[test]
+    fn profile_replaces_the_processing_list() {
+        let sources = [
+            "processing = [\"c2s\", \"contrast:30\"]\n[profiles.scan]\nprocessing = [\"rotate:90\"]",
+        ];
+        assert_eq!(
+            load(&sources, None, None).processing,
+            ["c2s", "contrast:30"]
+        );
+        assert_eq!(load(&sources, Some("scan"), None).processing, ["rotate:90"]);
+    }
+
+    #[test]
+    fn printer_sets_unprintable_and_calibration() {
+        let sources = [
+            "unprintable = { top = 1.0 }\n[printers.desk]\nunprintable = { bottom = 6.0 }\ncalibration = { scale_x = 1.01 }",
+        ];
+        let cfg = load(&sources, None, Some("desk"))
+            .config(PageMarginConfig::default())
+            .unwrap();
+        assert_eq!(cfg.unprintable.top, Mm(0.0));
+        assert_eq!(cfg.unprintable.bottom, Mm(6.0));
+        assert_eq!(cfg.calibration.scale_x, 1.01);
+    }
+
+    #[test]
+    fn unknown_profile_or_printer_lists_the_known_ones() {
+        let sources = ["[profiles.a]\nbleed = 1.0\n[profiles.b]\nbleed = 2.0"];
+        let err = FileSettings::from_tables(tables(&sources), Some("c"), None).unwrap_err();
+        assert_eq!(err, "Unknown profile `c`, the config files define: a, b.");
+        let err = FileSettings::from_tables(tables(&sources), None, Some("desk")).unwrap_err();
+        assert_eq!(
+            err,
+            "Unknown printer `desk`, the config files define: none."
+        );
+    }
+
+    #[test]
+    fn invalid_values_fail_on_loading() {
+        let err = FileSettings::from_tables(tables(&["bleed = \"wide\""]), None, None).unwrap_err();
+        assert!(err.starts_with("Invalid config"), "{}", err);
+        let err = FileSettings::from_tables(tables(&["colour = 1"]), None, None).unwrap_err();
+        assert!(err.contains("colour"), "{}", err);
+    }
+
+    #[test]
+    fn files_win_over_a_preset_and_keep_the_rest_of_it() {
+        let preset = PageMarginConfig::photo_sheet(CardProfile::PassportPhoto, PaperSize::Photo4x6);
+        let settings = load(
+            &["horizontal_spacing = 6.0\ncut_guides = { color = [1.0, 0.0, 0.0] }"],
+            None,
+            None,
+        );
+        let cfg = settings.config(preset.clone()).unwrap();
+        assert_eq!(cfg.horizontal_spacing, Mm(6.0));
+        assert_eq!(cfg.cut_guides.color, [1.0, 0.0, 0.0]);
+        assert_eq!(cfg.margin_top, preset.margin_top);
+        assert_eq!(cfg.page_width, preset.page_width);
+        assert_eq!(cfg.cut_guides.dash, None);
+        assert_eq!(cfg.cut_guides.width, preset.cut_guides.width);
+    }
+}
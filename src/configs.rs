@@ -1,5 +1,457 @@
-use printpdf::Mm;
+use std::str::FromStr;
 
+use printpdf::{Mm, Pt};
+use serde::{Deserialize, Serialize};
+
+/// Parses a length such as `85.6mm`, `3.37in`, `240pt` or `2cm`. A bare number is in mm.
+pub fn parse_length(s: &str) -> Result<Mm, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let value: f32 = number
+        .trim()
+        .parse()
+        .ok()
+        .filter(|value: &f32| value.is_finite())
+        .ok_or_else(|| format!("Invalid length `{}`, expected e.g. 85.6mm or 3.37in.", s))?;
+    let mm_per_unit = match unit.to_lowercase().as_str() {
+        "" | "mm" => 1.0,
+        "cm" => 10.0,
+        "in" => 25.4,
+        "pt" => 25.4 / 72.0,
+        _ => {
+            return Err(format!(
+                "Unknown unit `{}` in `{}`, use mm, cm, in or pt.",
+                unit, s
+            ));
+        }
+    };
+    Ok(Mm(value * mm_per_unit))
+}
+
+/// Which edge the sheet is turned over on between the front and back pass of a duplex print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplexFlip {
+    LongEdge,
+    ShortEdge,
+}
+
+/// Whether cards may be turned 90° to fit more copies on a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CardOrientation {
+    /// Picks whichever arrangement fits the most copies
+    Auto,
+    Upright,
+    Rotated,
+}
+
+/// How an image whose proportions differ from its slot is fitted into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FitMode {
+    /// Whole image inside the slot, centred with blank bars on two sides
+    Contain,
+    /// Fills the slot, centred with the overhanging edges cropped
+    Cover,
+    /// Fills the slot exactly, scaling each axis on its own
+    Stretch,
+    /// Printed at `source_dpi`, centred and cropped to the slot
+    ActualSize,
+}
+
+/// Centres the card grid on the page, margins and spacings then only act as minimum gutters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GridCentering {
+    None,
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+impl GridCentering {
+    pub fn horizontal(&self) -> bool {
+        matches!(self, Self::Horizontal | Self::Both)
+    }
+
+    pub fn vertical(&self) -> bool {
+        matches!(self, Self::Vertical | Self::Both)
+    }
+}
+
+/// How the cutting positions are marked on the sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CutGuideKind {
+    /// Dashed lines across the page between rows and columns
+    Lines,
+    /// Short ticks outside the corners of every card, nothing crosses the cut edges
+    CropMarks,
+    None,
+}
+
+/// How far guide lines run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GuideExtent {
+    /// Edge to edge across the page
+    Page,
+    /// Only across the card grid
+    Grid,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CutGuideStyle {
+    pub kind: CutGuideKind,
+    /// Dash and gap length in points for guide lines, `None` draws them solid
+    pub dash: Option<(i64, i64)>,
+    /// RGB components between 0 and 1
+    pub color: [f32; 3],
+    /// Stroke width, `None` uses 1pt for lines and 0.25pt for crop marks
+    pub width: Option<Pt>,
+    pub extent: GuideExtent,
+}
+
+impl Default for CutGuideStyle {
+    fn default() -> Self {
+        Self {
+            kind: CutGuideKind::Lines,
+            dash: Some((10, 5)),
+            color: [0.0, 0.0, 0.0],
+            width: None,
+            extent: GuideExtent::Grid,
+        }
+    }
+}
+
+/// Horizontal alignment of text lines on the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextAlign {
+    /// Starting at the left margin
+    Left,
+    Center,
+    /// Ending at the right margin
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TitleStyle {
+    pub font_size: Pt,
+    pub align: TextAlign,
+    /// Lines are wrapped at word boundaries to fit, `None` uses the width between the margins
+    pub max_width: Option<Mm>,
+}
+
+impl Default for TitleStyle {
+    fn default() -> Self {
+        Self {
+            font_size: Pt(20.0),
+            align: TextAlign::Center,
+            max_width: None,
+        }
+    }
+}
+
+/// Which side of the card its caption goes on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptionPosition {
+    Below,
+    Above,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptionStyle {
+    /// Text printed with every copy, `{index}`, `{total}`, `{title}` and `{date}` are replaced.
+    /// `None` prints no captions and reserves no space.
+    pub template: Option<String>,
+    pub position: CaptionPosition,
+    pub font_size: Pt,
+    /// Distance between the card (plus bleed) and the caption
+    pub gap: Mm,
+}
+
+impl Default for CaptionStyle {
+    fn default() -> Self {
+        Self {
+            template: None,
+            position: CaptionPosition::Below,
+            font_size: Pt(8.0),
+            gap: Mm(1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatermarkStyle {
+    /// Text drawn across every copy, `{date}` is replaced. `None` draws no watermark.
+    pub text: Option<String>,
+    /// Between 0 (invisible) and 1 (opaque)
+    pub opacity: f32,
+    /// RGB components between 0 and 1
+    pub color: [f32; 3],
+    /// Counter-clockwise rotation in degrees, `None` runs along the card diagonal
+    pub angle: Option<f32>,
+    /// `None` sizes the text to span the card
+    pub font_size: Option<Pt>,
+}
+
+impl Default for WatermarkStyle {
+    fn default() -> Self {
+        Self {
+            text: None,
+            opacity: 0.3,
+            color: [0.5, 0.5, 0.5],
+            angle: None,
+            font_size: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AttestationStyle {
+    /// Declaration above the signature, `{date}` is replaced. `None` leaves the block out.
+    pub declaration: Option<String>,
+    pub font_size: Pt,
+    /// Side of the square box left free for a rubber stamp
+    pub stamp_size: Mm,
+}
+
+impl Default for AttestationStyle {
+    fn default() -> Self {
+        Self {
+            declaration: None,
+            font_size: Pt(10.0),
+            stamp_size: Mm(30.0),
+        }
+    }
+}
+
+/// Distances from the four page edges.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EdgeInsets {
+    pub top: Mm,
+    pub right: Mm,
+    pub bottom: Mm,
+    pub left: Mm,
+}
+
+impl FromStr for EdgeInsets {
+    type Err = String;
+
+    /// Accepts one length for all edges or four as `top,right,bottom,left`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(parse_length)
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.iter().any(|value| *value < Mm(0.0)) {
+            return Err("Insets must not be negative.".into());
+        }
+        match values[..] {
+            [all] => Ok(Self {
+                top: all,
+                right: all,
+                bottom: all,
+                left: all,
+            }),
+            [top, right, bottom, left] => Ok(Self {
+                top,
+                right,
+                bottom,
+                left,
+            }),
+            _ => Err("Expected one length or four as top,right,bottom,left.".into()),
+        }
+    }
+}
+
+/// Corrections for a printer that shifts or scales its output, applied to everything on the page
+/// so copies come out at true size.
+///
+/// A card printed 85.2 mm wide instead of 85.6 mm needs `scale_x = 85.6 / 85.2`, and content
+/// landing 1 mm too far left needs `offset_x = 1.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrinterCalibration {
+    /// Shift to the right and up
+    pub offset_x: Mm,
+    pub offset_y: Mm,
+    /// Scale around the page centre
+    pub scale_x: f32,
+    pub scale_y: f32,
+}
+
+impl Default for PrinterCalibration {
+    fn default() -> Self {
+        Self {
+            offset_x: Mm(0.0),
+            offset_y: Mm(0.0),
+            scale_x: 1.0,
+            scale_y: 1.0,
+        }
+    }
+}
+
+impl PrinterCalibration {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What a printer profile stores about one device.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrinterProfile {
+    /// Strips along the paper edges the printer cannot reach
+    pub unprintable: EdgeInsets,
+    pub calibration: PrinterCalibration,
+}
+
+/// Named paper sizes, dimensions are in portrait orientation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaperSize {
+    A3,
+    A4,
+    A5,
+    A6,
+    Letter,
+    Legal,
+    /// 4x6 inch photo paper
+    Photo4x6,
+    Custom(Mm, Mm),
+}
+
+impl PaperSize {
+    /// Width and height in portrait orientation.
+    pub fn dimensions(&self) -> (Mm, Mm) {
+        let (width, height) = match self {
+            Self::A3 => (Mm(297.0), Mm(420.0)),
+            Self::A4 => (Mm(210.0), Mm(297.0)),
+            Self::A5 => (Mm(148.0), Mm(210.0)),
+            Self::A6 => (Mm(105.0), Mm(148.0)),
+            Self::Letter => (Mm(215.9), Mm(279.4)),
+            Self::Legal => (Mm(215.9), Mm(355.6)),
+            Self::Photo4x6 => (Mm(101.6), Mm(152.4)),
+            Self::Custom(width, height) => (*width, *height),
+        };
+        (width.min(height), width.max(height))
+    }
+}
+
+impl FromStr for PaperSize {
+    type Err = String;
+
+    /// Accepts a preset name or `custom:WIDTHxHEIGHT`, in millimetres unless given a unit.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        if let Some(size) = lower.strip_prefix("custom:") {
+            let (width, height) = size
+                .split_once('x')
+                .ok_or("Expected custom paper as `custom:WIDTHxHEIGHT`, e.g. custom:100x150.")?;
+            let (width, height) = (parse_length(width)?, parse_length(height)?);
+            if width <= Mm(0.0) || height <= Mm(0.0) {
+                return Err("Paper dimensions must be positive.".into());
+            }
+            return Ok(Self::Custom(width, height));
+        }
+
+        Ok(match lower.as_str() {
+            "a3" => Self::A3,
+            "a4" => Self::A4,
+            "a5" => Self::A5,
+            "a6" => Self::A6,
+            "letter" => Self::Letter,
+            "legal" => Self::Legal,
+            "4x6" | "photo-4x6" => Self::Photo4x6,
+            _ => return Err(format!("Unknown paper size `{}`.", s)),
+        })
+    }
+}
+
+/// Standard document and photo sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CardProfile {
+    /// ISO/IEC 7810 ID-1 (CR80), national ID and bank cards
+    #[value(name = "id1")]
+    Id1,
+    /// ISO/IEC 7810 ID-2, older ID documents
+    #[value(name = "id2")]
+    Id2,
+    /// ISO/IEC 7810 ID-3, passport data pages
+    #[value(name = "passport-id3")]
+    PassportId3,
+    /// 35x45 mm passport photo
+    PassportPhoto,
+    /// 2x2 inch US visa photo
+    UsVisa,
+    /// 33x48 mm visa photo, e.g. China
+    #[value(name = "photo-33x48")]
+    Photo33x48,
+    BusinessCard,
+}
+
+impl CardProfile {
+    /// Width and height of the document in its reading orientation.
+    pub fn dimensions(&self) -> (Mm, Mm) {
+        match self {
+            Self::Id1 => (Mm(85.6), Mm(54.0)),
+            Self::Id2 => (Mm(105.0), Mm(74.0)),
+            Self::PassportId3 => (Mm(125.0), Mm(88.0)),
+            Self::PassportPhoto => (Mm(35.0), Mm(45.0)),
+            Self::UsVisa => (Mm(50.8), Mm(50.8)),
+            Self::Photo33x48 => (Mm(33.0), Mm(48.0)),
+            Self::BusinessCard => (Mm(89.0), Mm(51.0)),
+        }
+    }
+
+    /// Photos stay upright so every tile reads the same way, documents may turn to fit more.
+    pub fn orientation(&self) -> CardOrientation {
+        match self {
+            Self::PassportPhoto | Self::UsVisa | Self::Photo33x48 => CardOrientation::Upright,
+            _ => CardOrientation::Auto,
+        }
+    }
+
+    /// Changes the parts of `style` the document calls for, the rest stay as configured.
+    pub fn apply_cut_guides(&self, style: &mut CutGuideStyle) {
+        match self {
+            // Thin solid lines between photo tiles
+            Self::PassportPhoto | Self::UsVisa | Self::Photo33x48 => {
+                style.dash = None;
+                style.width = Some(Pt(0.5));
+            }
+            // Print shops trim business cards on the crop marks
+            Self::BusinessCard => style.kind = CutGuideKind::CropMarks,
+            _ => {}
+        }
+    }
+
+    /// Accepted chin-to-crown height on photo profiles.
+    pub fn head_height(&self) -> Option<(Mm, Mm)> {
+        match self {
+            Self::PassportPhoto => Some((Mm(32.0), Mm(36.0))),
+            // 1 to 1 3/8 inch
+            Self::UsVisa => Some((Mm(25.4), Mm(34.9))),
+            Self::Photo33x48 => Some((Mm(28.0), Mm(33.0))),
+            _ => None,
+        }
+    }
+}
+
+/// Page geometry and drawing options. Config files set any of these fields by name, the rest
+/// keep their defaults.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct PageMarginConfig {
     pub page_width: Mm,
     pub page_height: Mm,
@@ -10,6 +462,37 @@ pub struct PageMarginConfig {
     pub margin_bottom: Mm,
     pub horizontal_spacing: Mm,
     pub vertical_spacing: Mm,
+    /// Extra image around every card so cutting on the trim line leaves no white slivers
+    pub bleed: Mm,
+    /// Inset from the trim line that important content should stay within
+    pub safe_zone: Mm,
+    pub show_safe_zone: bool,
+    /// Corner radius images are clipped to, zero keeps them square
+    pub corner_radius: Mm,
+    /// Stroke width of a frame along the trim line, `None` draws no frame
+    pub border: Option<Pt>,
+    pub cut_guides: CutGuideStyle,
+    /// Circle-and-cross targets in the page corners
+    pub registration_marks: bool,
+    /// Strips along the page edges the printer cannot reach, only checked by the layout
+    /// validation
+    pub unprintable: EdgeInsets,
+    /// Offset and scale correction for the printer, applied to all page content
+    pub calibration: PrinterCalibration,
+    pub title: TitleStyle,
+    pub caption: CaptionStyle,
+    pub watermark: WatermarkStyle,
+    /// "Certified true copy" block below the grid
+    pub attestation: AttestationStyle,
+    pub card_orientation: CardOrientation,
+    pub fit: FitMode,
+    /// Resolution the source images were scanned at, for `FitMode::ActualSize`
+    pub source_dpi: f32,
+    pub centering: GridCentering,
+    pub duplex_flip: DuplexFlip,
+    /// Shifts the back side to correct the printer's front-to-back registration
+    pub duplex_offset_x: Mm,
+    pub duplex_offset_y: Mm,
 }
 
 impl Default for PageMarginConfig {
@@ -24,6 +507,234 @@ impl Default for PageMarginConfig {
             margin_bottom: Mm(10.0),
             horizontal_spacing: Mm(18.0),
             vertical_spacing: Mm(10.0),
+            bleed: Mm(0.0),
+            safe_zone: Mm(3.0),
+            show_safe_zone: false,
+            corner_radius: Mm(0.0),
+            border: None,
+            cut_guides: CutGuideStyle::default(),
+            registration_marks: false,
+            unprintable: EdgeInsets::default(),
+            calibration: PrinterCalibration::default(),
+            title: TitleStyle::default(),
+            caption: CaptionStyle::default(),
+            watermark: WatermarkStyle::default(),
+            attestation: AttestationStyle::default(),
+            card_orientation: CardOrientation::Auto,
+            fit: FitMode::Stretch,
+            source_dpi: 300.0,
+            centering: GridCentering::None,
+            duplex_flip: DuplexFlip::LongEdge,
+            duplex_offset_x: Mm(0.0),
+            duplex_offset_y: Mm(0.0),
         }
     }
 }
+
+impl PageMarginConfig {
+    /// Tiles of one photo profile on photo paper, centred with thin cut lines between them.
+    pub fn photo_sheet(profile: CardProfile, paper: PaperSize) -> Self {
+        let mut cfg = Self {
+            margin_top: Mm(5.0),
+            margin_left: Mm(5.0),
+            margin_bottom: Mm(5.0),
+            horizontal_spacing: Mm(2.0),
+            vertical_spacing: Mm(2.0),
+            centering: GridCentering::Both,
+            ..Default::default()
+        };
+        cfg.set_paper(paper, false);
+        cfg.set_card_profile(profile);
+        cfg
+    }
+
+    /// Sets the card dimensions and orientation from a document profile, along with the cut
+    /// guide settings the profile calls for.
+    pub fn set_card_profile(&mut self, profile: CardProfile) {
+        (self.card_width, self.card_height) = profile.dimensions();
+        self.card_orientation = profile.orientation();
+        profile.apply_cut_guides(&mut self.cut_guides);
+    }
+
+    /// Sets the page dimensions from a paper preset.
+    pub fn set_paper(&mut self, paper: PaperSize, landscape: bool) {
+        let (width, height) = paper.dimensions();
+        (self.page_width, self.page_height) = if landscape {
+            (height, width)
+        } else {
+            (width, height)
+        };
+    }
+
+    /// Rejects page, margin and card dimensions that cannot produce a layout, naming the fields
+    /// involved.
+    pub fn check_dimensions(&self) -> Result<(), String> {
+        let lengths = [
+            ("page_width", self.page_width),
+            ("page_height", self.page_height),
+            ("card_width", self.card_width),
+            ("card_height", self.card_height),
+        ];
+        if let Some((name, value)) = lengths.iter().find(|(_, value)| *value <= Mm(0.0)) {
+            return Err(format!("{} must be positive, got {} mm.", name, value.0));
+        }
+        if !(self.source_dpi > 0.0 && self.source_dpi.is_finite()) {
+            return Err(format!(
+                "source_dpi must be positive, got {}.",
+                self.source_dpi
+            ));
+        }
+        let gaps = [
+            ("margin_top", self.margin_top),
+            ("margin_left", self.margin_left),
+            ("margin_bottom", self.margin_bottom),
+            ("horizontal_spacing", self.horizontal_spacing),
+            ("vertical_spacing", self.vertical_spacing),
+        ];
+        if let Some((name, value)) = gaps.iter().find(|(_, value)| *value < Mm(0.0)) {
+            return Err(format!(
+                "{} must not be negative, got {} mm.",
+                name, value.0
+            ));
+        }
+
+        // The left margin is mirrored on the right
+        let free_width = self.page_width - self.margin_left * 2.0;
+        let free_height = self.page_height - self.margin_top - self.margin_bottom;
+        if free_width <= Mm(0.0) {
+            return Err(format!(
+                "margin_left of {} mm on both sides leaves no room on the {} mm wide page.",
+                self.margin_left.0, self.page_width.0
+            ));
+        }
+        if free_height <= Mm(0.0) {
+            return Err(format!(
+                "margin_top and margin_bottom of {} + {} mm leave no room on the {} mm high page.",
+                self.margin_top.0, self.margin_bottom.0, self.page_height.0
+            ));
+        }
+
+        let fits = |width: Mm, height: Mm| width <= free_width && height <= free_height;
+        let fits = match self.card_orientation {
+            CardOrientation::Upright => fits(self.card_width, self.card_height),
+            CardOrientation::Rotated => fits(self.card_height, self.card_width),
+            CardOrientation::Auto => {
+                fits(self.card_width, self.card_height) || fits(self.card_height, self.card_width)
+            }
+        };
+        if !fits {
+            return Err(format!(
+                "A {} x {} mm card (card_width x card_height) does not fit in the {} x {} mm \
+                 between the margins (page_width, page_height, margin_left, margin_top, \
+                 margin_bottom).",
+                self.card_width.0, self.card_height.0, free_width.0, free_height.0
+            ));
+        }
+        Ok(())
+    }
+
+    /// Where the calibrated printer puts a point of the page.
+    pub fn device_point(&self, x: Mm, y: Mm) -> (Mm, Mm) {
+        let calibration = &self.calibration;
+        let (center_x, center_y) = (self.page_width / 2.0, self.page_height / 2.0);
+        (
+            center_x + (x - center_x) * calibration.scale_x + calibration.offset_x,
+            center_y + (y - center_y) * calibration.scale_y + calibration.offset_y,
+        )
+    }
+
+    /// Space kept free below and above every card for its caption, zero without captions.
+    pub fn caption_bands(&self) -> (Mm, Mm) {
+        if self.caption.template.is_none() {
+            return (Mm(0.0), Mm(0.0));
+        }
+        let band = self.bleed + self.caption.gap + Mm::from(self.caption.font_size) * 1.2;
+        match self.caption.position {
+            CaptionPosition::Below => (band, Mm(0.0)),
+            CaptionPosition::Above => (Mm(0.0), band),
+        }
+    }
+
+    /// Gaps between card slots, widened when needed so the bleeds of neighbours do not overlap.
+    pub fn slot_spacing(&self) -> (Mm, Mm) {
+        let min_spacing = self.bleed * 2.0;
+        (
+            self.horizontal_spacing.max(min_spacing),
+            self.vertical_spacing.max(min_spacing),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_mm(actual: Mm, expected: f32) {
+        assert!(
+            (actual.0 - expected).abs() < 1e-4,
+            "expected {} mm, got {} mm",
+            expected,
+            actual.0
+        );
+    }
+
+    #[test]
+    fn card_profile_keeps_the_configured_guide_colour() {
+        let mut cfg = PageMarginConfig {
+            cut_guides: CutGuideStyle {
+                color: [1.0, 0.0, 0.0],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        cfg.set_card_profile(CardProfile::BusinessCard);
+        assert_eq!(cfg.cut_guides.kind, CutGuideKind::CropMarks);
+        assert_eq!(cfg.cut_guides.color, [1.0, 0.0, 0.0]);
+        assert_eq!(cfg.card_width, Mm(89.0));
+    }
+
+    #[test]
+    fn parse_length_converts_units() {
+        assert_mm(parse_length("85.6").unwrap(), 85.6);
+        assert_mm(parse_length("85.6mm").unwrap(), 85.6);
+        assert_mm(parse_length("2cm").unwrap(), 20.0);
+        assert_mm(parse_length(" 1in ").unwrap(), 25.4);
+        assert_mm(parse_length("72pt").unwrap(), 25.4);
+        assert_mm(parse_length("3.37 IN").unwrap(), 85.598);
+        assert_mm(parse_length("-4mm").unwrap(), -4.0);
+    }
+
+    #[test]
+    fn parse_length_rejects_garbage() {
+        assert!(parse_length("").is_err());
+        assert!(parse_length("mm").is_err());
+        assert!(parse_length("12ft").is_err());
+        assert!(parse_length("inf").is_err());
+        assert!(parse_length("1.2.3mm").is_err());
+    }
+
+    #[test]
+    fn edge_insets_from_one_length() {
+        let insets: EdgeInsets = "4mm".parse().unwrap();
+        for edge in [insets.top, insets.right, insets.bottom, insets.left] {
+            assert_mm(edge, 4.0);
+        }
+    }
+
+    #[test]
+    fn edge_insets_from_four_lengths() {
+        let insets: EdgeInsets = "4mm, 0.5cm,12,1in".parse().unwrap();
+        assert_mm(insets.top, 4.0);
+        assert_mm(insets.right, 5.0);
+        assert_mm(insets.bottom, 12.0);
+        assert_mm(insets.left, 25.4);
+    }
+
+    #[test]
+    fn edge_insets_rejects_bad_input() {
+        assert!("4mm,4mm".parse::<EdgeInsets>().is_err());
+        assert!("1,2,3,4,5".parse::<EdgeInsets>().is_err());
+        assert!("-1mm".parse::<EdgeInsets>().is_err());
+        assert!("4mm,,4mm,4mm".parse::<EdgeInsets>().is_err());
+    }
+}
@@ -8,6 +8,15 @@ use imageproc::distance_transform::Norm;
 use imageproc::morphology::close;
 use imageproc::rect::Rect;
 
+/// Where the head sits in a source portrait, in pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct HeadPosition {
+    pub crown_y: u32,
+    pub chin_y: u32,
+    /// Horizontal centre of the face, the image centre when `None`
+    pub center_x: Option<u32>,
+}
+
 pub struct ImgProcUtils {}
 
 impl ImgProcUtils {
@@ -61,6 +70,54 @@ impl ImgProcUtils {
         Some(cropped)
     }
 
+    /// Crop the largest centred area with the given `aspect` (width / height).
+    pub fn crop_to_aspect(input: &DynamicImage, aspect: f32) -> DynamicImage {
+        let (width, height) = (input.width() as f32, input.height() as f32);
+        let (crop_w, crop_h) = if width / height > aspect {
+            (height * aspect, height)
+        } else {
+            (width, width / aspect)
+        };
+
+        input.crop_imm(
+            ((width - crop_w) / 2.0) as u32,
+            ((height - crop_h) / 2.0) as u32,
+            crop_w as u32,
+            crop_h as u32,
+        )
+    }
+
+    /// Crop a portrait to `aspect` (width / height) so the head fills `head_ratio` of the output
+    /// height and the crown sits `top_ratio` of the height below the top edge.
+    /// Areas outside the source are filled white, like a photo backdrop.
+    pub fn crop_to_head(
+        input: &DynamicImage,
+        aspect: f32,
+        head: &HeadPosition,
+        head_ratio: f32,
+        top_ratio: f32,
+    ) -> DynamicImage {
+        let head_px = head.chin_y.saturating_sub(head.crown_y).max(1) as f32;
+        let crop_h = head_px / head_ratio;
+        let crop_w = crop_h * aspect;
+        let center_x = head.center_x.unwrap_or(input.width() / 2) as f32;
+        let left = center_x - crop_w / 2.0;
+        let top = head.crown_y as f32 - crop_h * top_ratio;
+
+        let mut canvas = RgbaImage::from_pixel(
+            crop_w.round() as u32,
+            crop_h.round() as u32,
+            Rgba([255, 255, 255, 255]),
+        );
+        image::imageops::overlay(
+            &mut canvas,
+            &input.to_rgba8(),
+            -left.round() as i64,
+            -top.round() as i64,
+        );
+        DynamicImage::ImageRgba8(canvas)
+    }
+
     /// Adjust brightness and contrast of an image.
     /// `brightness`: -255 to +255
     /// `contrast`: -127 to +127
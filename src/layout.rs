@@ -0,0 +1,488 @@
+use std::cmp::Reverse;
+
+use printpdf::Mm;
+
+use crate::configs::{CardOrientation, DuplexFlip, GridCentering, PageMarginConfig};
+
+/// A single card slot on a page, measured from the bottom left corner.
+#[derive(Debug, Clone, Copy)]
+pub struct SlotRect {
+    pub x: Mm,
+    pub y: Mm,
+    pub width: Mm,
+    pub height: Mm,
+    /// Counter-clockwise turn of the card in degrees, 0, 90 or 270. `width`/`height` are its
+    /// footprint on the page.
+    pub rotation: u16,
+}
+
+impl SlotRect {
+    pub fn right(&self) -> Mm {
+        self.x + self.width
+    }
+
+    pub fn top(&self) -> Mm {
+        self.y + self.height
+    }
+
+    /// The card lies across its slot.
+    pub fn is_rotated(&self) -> bool {
+        self.rotation % 180 == 90
+    }
+
+    /// Shrinks the rectangle on every side.
+    pub fn inset(&self, amount: Mm) -> SlotRect {
+        SlotRect {
+            x: self.x + amount,
+            y: self.y + amount,
+            width: self.width - amount * 2.0,
+            height: self.height - amount * 2.0,
+            ..*self
+        }
+    }
+
+    /// Grows the rectangle on every side.
+    pub fn outset(&self, amount: Mm) -> SlotRect {
+        self.inset(amount * -1.0)
+    }
+}
+
+/// Decides where the card copies go on a page.
+pub trait LayoutStrategy {
+    /// Slot rectangles of one page, in placement order.
+    fn page_slots(&self, cfg: &PageMarginConfig, card_width: Mm, card_height: Mm) -> Vec<SlotRect>;
+}
+
+/// Rows and columns computed from the page geometry, filled left to right and top to bottom.
+///
+/// With `CardOrientation::Auto` both card orientations are tried, along with an upright grid
+/// topped up by rotated cards in the space it leaves (and the other way round), and the
+/// arrangement that fits the most copies wins.
+pub struct GridLayout;
+
+impl GridLayout {
+    /// How many cells of `cell` size separated by `spacing` fit into `available`.
+    fn fit_count(available: Mm, cell: Mm, spacing: Mm) -> usize {
+        if cell.0 <= 0.0 || available < cell {
+            return 0;
+        }
+        ((available.0 + spacing.0) / (cell.0 + spacing.0)).floor() as usize
+    }
+
+    /// Height of a grid row, the card footprint plus its caption bands.
+    fn row_height(cfg: &PageMarginConfig, height: Mm) -> Mm {
+        let (below, above) = cfg.caption_bands();
+        height + below + above
+    }
+
+    /// Columns and rows of `width` x `height` cells that fit into `area`.
+    fn grid_size(cfg: &PageMarginConfig, area: &SlotRect, width: Mm, height: Mm) -> (usize, usize) {
+        let (h_spacing, v_spacing) = cfg.slot_spacing();
+        (
+            Self::fit_count(area.width, width, h_spacing),
+            Self::fit_count(area.height, Self::row_height(cfg, height), v_spacing),
+        )
+    }
+
+    /// Fills `area` from its top left corner with cells of the given footprint.
+    fn fill(
+        cfg: &PageMarginConfig,
+        area: &SlotRect,
+        width: Mm,
+        height: Mm,
+        rotated: bool,
+    ) -> Vec<SlotRect> {
+        let (columns, rows) = Self::grid_size(cfg, area, width, height);
+        let (h_spacing, v_spacing) = cfg.slot_spacing();
+        let (_, above) = cfg.caption_bands();
+        let row_height = Self::row_height(cfg, height);
+
+        let mut slots = Vec::with_capacity(rows * columns);
+        for row in 0..rows {
+            for column in 0..columns {
+                slots.push(SlotRect {
+                    x: area.x + (width + h_spacing) * column as f32,
+                    y: area.top() - above - height - (row_height + v_spacing) * row as f32,
+                    width,
+                    height,
+                    rotation: if rotated { 90 } else { 0 },
+                });
+            }
+        }
+        slots
+    }
+
+    /// A full grid of one orientation, followed by the other orientation in the leftover band
+    /// below or to the right of it, whichever holds more.
+    fn fill_mixed(
+        cfg: &PageMarginConfig,
+        area: &SlotRect,
+        (width, height): (Mm, Mm),
+        rotated: bool,
+    ) -> Vec<SlotRect> {
+        let (columns, rows) = Self::grid_size(cfg, area, width, height);
+        let (h_spacing, v_spacing) = cfg.slot_spacing();
+        let mut slots = Self::fill(cfg, area, width, height, rotated);
+        if slots.is_empty() {
+            return slots;
+        }
+
+        let used_height = (Self::row_height(cfg, height) + v_spacing) * rows as f32;
+        let below = SlotRect {
+            height: area.height - used_height,
+            ..*area
+        };
+        let used_width = (width + h_spacing) * columns as f32;
+        let right = SlotRect {
+            x: area.x + used_width,
+            width: area.width - used_width,
+            ..*area
+        };
+
+        let below = Self::fill(cfg, &below, height, width, !rotated);
+        let right = Self::fill(cfg, &right, height, width, !rotated);
+        slots.extend(if right.len() > below.len() {
+            right
+        } else {
+            below
+        });
+        slots
+    }
+}
+
+impl LayoutStrategy for GridLayout {
+    fn page_slots(&self, cfg: &PageMarginConfig, card_width: Mm, card_height: Mm) -> Vec<SlotRect> {
+        let area = page_area(cfg);
+
+        let slots = match cfg.card_orientation {
+            CardOrientation::Upright => Self::fill(cfg, &area, card_width, card_height, false),
+            CardOrientation::Rotated => Self::fill(cfg, &area, card_height, card_width, true),
+            CardOrientation::Auto => {
+                let candidates = [
+                    Self::fill(cfg, &area, card_width, card_height, false),
+                    Self::fill(cfg, &area, card_height, card_width, true),
+                    Self::fill_mixed(cfg, &area, (card_width, card_height), false),
+                    Self::fill_mixed(cfg, &area, (card_height, card_width), true),
+                ];
+                // Prefer the earlier, simpler arrangement when counts are equal
+                candidates
+                    .into_iter()
+                    .reduce(|best, next| if next.len() > best.len() { next } else { best })
+                    .unwrap_or_default()
+            }
+        };
+        center_slots(slots, cfg.page_width, cfg.page_height, cfg.centering)
+    }
+}
+
+/// Printable area inside the margins, a right margin equal to the left one is implied.
+fn page_area(cfg: &PageMarginConfig) -> SlotRect {
+    SlotRect {
+        x: cfg.margin_left,
+        y: cfg.margin_bottom,
+        width: cfg.page_width - cfg.margin_left * 2.0,
+        height: cfg.page_height - cfg.margin_top - cfg.margin_bottom,
+        rotation: 0,
+    }
+}
+
+/// A row of pieces of at most `height`, hanging from `top`.
+struct Shelf {
+    top: Mm,
+    height: Mm,
+    used_width: Mm,
+}
+
+#[derive(Default)]
+struct PackedPage {
+    shelves: Vec<Shelf>,
+    /// Index of the piece and where it went
+    placed: Vec<(usize, SlotRect)>,
+}
+
+/// Packs pieces of different sizes onto as few pages as possible, returning per page the index
+/// of each placed piece in `sizes` and its slot.
+///
+/// Shelves are filled first-fit with the tallest pieces first. With `CardOrientation::Auto` a
+/// piece lies on its long side unless only the upright orientation fits the page, and stands up
+/// when that is the only way into an existing shelf. Pieces too big for an empty page are left
+/// out.
+pub fn pack_pieces(cfg: &PageMarginConfig, sizes: &[(Mm, Mm)]) -> Vec<Vec<(usize, SlotRect)>> {
+    let area = page_area(cfg);
+    let (h_spacing, v_spacing) = cfg.slot_spacing();
+    let fits = |width: Mm, height: Mm| width <= area.width && height <= area.height;
+
+    let mut pieces: Vec<(usize, Mm, Mm, bool)> = sizes
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, &(width, height))| {
+            let rotated = match cfg.card_orientation {
+                CardOrientation::Upright => false,
+                CardOrientation::Rotated => true,
+                CardOrientation::Auto => {
+                    (height > width && fits(height, width)) || !fits(width, height)
+                }
+            };
+            let (width, height) = if rotated {
+                (height, width)
+            } else {
+                (width, height)
+            };
+            fits(width, height).then_some((idx, width, height, rotated))
+        })
+        .collect();
+    pieces.sort_by_key(|&(_, _, height, _)| Reverse(height));
+
+    let mut pages: Vec<PackedPage> = Vec::new();
+    for (idx, width, height, rotated) in pieces {
+        // An Auto piece may also turn to fill the end of a shelf
+        let mut orientations = vec![(width, height, rotated)];
+        if cfg.card_orientation == CardOrientation::Auto && fits(height, width) {
+            orientations.push((height, width, !rotated));
+        }
+        let shelf_fits = |shelf: &Shelf, (width, height, _): (Mm, Mm, bool)| {
+            let gap = if shelf.used_width > Mm(0.0) {
+                h_spacing
+            } else {
+                Mm(0.0)
+            };
+            height <= shelf.height && shelf.used_width + gap + width <= area.width
+        };
+        // Existing shelf, else a new shelf under the last one, else a new page
+        let found = pages.iter().enumerate().find_map(|(page, packed)| {
+            packed.shelves.iter().enumerate().find_map(|(shelf, row)| {
+                orientations
+                    .iter()
+                    .find(|&&orientation| shelf_fits(row, orientation))
+                    .map(|&orientation| (page, shelf, orientation))
+            })
+        });
+        let (page, shelf, (width, height, rotated)) = match found {
+            Some(found) => found,
+            None => {
+                let next_top = |shelves: &Vec<Shelf>| {
+                    shelves
+                        .last()
+                        .map_or(area.top(), |last| last.top - last.height - v_spacing)
+                };
+                let page = pages
+                    .iter()
+                    .position(|packed| next_top(&packed.shelves) - height >= area.y)
+                    .unwrap_or_else(|| {
+                        pages.push(PackedPage::default());
+                        pages.len() - 1
+                    });
+                let shelves = &mut pages[page].shelves;
+                shelves.push(Shelf {
+                    top: next_top(shelves),
+                    height,
+                    used_width: Mm(0.0),
+                });
+                (page, shelves.len() - 1, (width, height, rotated))
+            }
+        };
+
+        let PackedPage { shelves, placed } = &mut pages[page];
+        let shelf = &mut shelves[shelf];
+        if shelf.used_width > Mm(0.0) {
+            shelf.used_width += h_spacing;
+        }
+        placed.push((
+            idx,
+            SlotRect {
+                x: area.x + shelf.used_width,
+                y: shelf.top - height,
+                width,
+                height,
+                rotation: if rotated { 90 } else { 0 },
+            },
+        ));
+        shelf.used_width += width;
+    }
+
+    pages
+        .into_iter()
+        .map(|packed| {
+            let (indices, slots): (Vec<usize>, Vec<SlotRect>) = packed.placed.into_iter().unzip();
+            let slots = center_slots(slots, cfg.page_width, cfg.page_height, cfg.centering);
+            indices.into_iter().zip(slots).collect()
+        })
+        .collect()
+}
+
+/// Shifts the slots so their bounding box sits in the middle of the page.
+pub fn center_slots(
+    mut slots: Vec<SlotRect>,
+    page_width: Mm,
+    page_height: Mm,
+    centering: GridCentering,
+) -> Vec<SlotRect> {
+    let Some(first) = slots.first() else {
+        return slots;
+    };
+    let (mut left, mut bottom, mut right, mut top) = (first.x, first.y, first.right(), first.top());
+    for slot in &slots {
+        left = left.min(slot.x);
+        bottom = bottom.min(slot.y);
+        right = right.max(slot.right());
+        top = top.max(slot.top());
+    }
+
+    let dx = if centering.horizontal() {
+        (page_width - (right - left)) / 2.0 - left
+    } else {
+        Mm(0.0)
+    };
+    let dy = if centering.vertical() {
+        (page_height - (top - bottom)) / 2.0 - bottom
+    } else {
+        Mm(0.0)
+    };
+
+    for slot in &mut slots {
+        slot.x += dx;
+        slot.y += dy;
+    }
+    slots
+}
+
+/// Where the cut guides of a set of slots go: x positions of the vertical and y positions of
+/// the horizontal guides.
+///
+/// Without a bleed a guide sits in the middle of the gap between neighbouring rows or columns,
+/// and half a gap outside the outermost ones. With a bleed the gap is image, so guides go on
+/// both trim lines instead. Captions stay on the piece, so rows are cut outside their bands.
+pub fn cut_positions(slots: &[SlotRect], cfg: &PageMarginConfig) -> (Vec<Mm>, Vec<Mm>) {
+    let (h_spacing, v_spacing) = cfg.slot_spacing();
+    let (below, above) = cfg.caption_bands();
+    let trim = cfg.bleed > Mm(0.0);
+    let columns = slots.iter().map(|slot| (slot.x, slot.right()));
+    let rows = slots
+        .iter()
+        .map(|slot| (slot.y - below, slot.top() + above));
+    (
+        band_edges(columns, h_spacing, trim),
+        band_edges(rows, v_spacing, trim),
+    )
+}
+
+fn band_edges(bands: impl Iterator<Item = (Mm, Mm)>, spacing: Mm, trim: bool) -> Vec<Mm> {
+    let offset = if trim { Mm(0.0) } else { spacing / 2.0 };
+    let mut edges: Vec<Mm> = bands
+        .flat_map(|(start, end)| [start - offset, end + offset])
+        .collect();
+    edges.sort();
+    // Neighbours share the guide in the middle of their gap, up to rounding
+    edges.dedup_by(|a, b| (a.0 - b.0).abs() < 0.01);
+    edges
+}
+
+/// Moves front-side slots to the positions directly behind them on the back side of the sheet.
+///
+/// Turning the sheet over mirrors a rotated card along its length, so its back is turned the
+/// other way round to keep the same top edge as its front.
+pub fn mirror_for_duplex(slots: &[SlotRect], cfg: &PageMarginConfig) -> Vec<SlotRect> {
+    let flip_rows = duplex_flips_rows(cfg);
+    slots
+        .iter()
+        .map(|slot| {
+            let (x, y) = if flip_rows {
+                (slot.x, cfg.page_height - slot.y - slot.height)
+            } else {
+                (cfg.page_width - slot.x - slot.width, slot.y)
+            };
+            SlotRect {
+                x: x + cfg.duplex_offset_x,
+                y: y + cfg.duplex_offset_y,
+                rotation: (360 - slot.rotation) % 360,
+                ..*slot
+            }
+        })
+        .collect()
+}
+
+/// Whether turning the sheet over for duplex swaps the top and bottom of the page rather than
+/// its left and right.
+pub fn duplex_flips_rows(cfg: &PageMarginConfig) -> bool {
+    // The long edge runs vertically on portrait pages, so flipping over it mirrors the columns
+    (cfg.duplex_flip == DuplexFlip::LongEdge) != (cfg.page_height >= cfg.page_width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::check_slots;
+
+    const ID1: (Mm, Mm) = (Mm(85.6), Mm(54.0));
+
+    fn slots(page: &[(usize, SlotRect)]) -> Vec<SlotRect> {
+        page.iter().map(|(_, slot)| *slot).collect()
+    }
+
+    #[test]
+    fn packs_shelves_from_the_top_left() {
+        let cfg = PageMarginConfig::default();
+        let pages = pack_pieces(&cfg, &[ID1; 3]);
+        assert_eq!(pages.len(), 1);
+        let placed: Vec<(f32, f32)> = pages[0]
+            .iter()
+            .map(|(_, slot)| (slot.x.0, slot.y.0))
+            .collect();
+        assert_eq!(placed, [(10.0, 233.0), (113.6, 233.0), (10.0, 169.0)]);
+    }
+
+    #[test]
+    fn spills_onto_new_pages_without_overlaps() {
+        let cfg = PageMarginConfig::default();
+        let sizes = [ID1; 10];
+        let pages = pack_pieces(&cfg, &sizes);
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [8, 2]);
+        for page in &pages {
+            assert_eq!(check_slots(&cfg, &slots(page), "front"), Vec::new());
+        }
+        let mut indices: Vec<usize> = pages.iter().flatten().map(|&(idx, _)| idx).collect();
+        indices.sort_unstable();
+        assert_eq!(indices, (0..sizes.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn tallest_pieces_go_first() {
+        let cfg = PageMarginConfig::default();
+        let pages = pack_pieces(&cfg, &[ID1, (Mm(70.0), Mm(80.0))]);
+        let order: Vec<usize> = pages[0].iter().map(|&(idx, _)| idx).collect();
+        assert_eq!(order, [1, 0]);
+        // The card fits beside the taller piece on its shelf
+        assert_eq!(pages[0][1].1.y, Mm(287.0 - 54.0));
+    }
+
+    #[test]
+    fn auto_lays_pieces_on_their_long_side() {
+        let cfg = PageMarginConfig::default();
+        let pages = pack_pieces(&cfg, &[(Mm(54.0), Mm(85.6))]);
+        let slot = pages[0][0].1;
+        assert_eq!((slot.width, slot.height), (Mm(85.6), Mm(54.0)));
+        assert!(slot.is_rotated());
+    }
+
+    #[test]
+    fn upright_keeps_pieces_as_given() {
+        let cfg = PageMarginConfig {
+            card_orientation: CardOrientation::Upright,
+            ..PageMarginConfig::default()
+        };
+        let pages = pack_pieces(&cfg, &[(Mm(54.0), Mm(85.6))]);
+        let slot = pages[0][0].1;
+        assert_eq!((slot.width, slot.height), (Mm(54.0), Mm(85.6)));
+        assert_eq!(slot.rotation, 0);
+    }
+
+    #[test]
+    fn leaves_out_pieces_too_big_for_the_page() {
+        let cfg = PageMarginConfig::default();
+        let pages = pack_pieces(&cfg, &[(Mm(300.0), Mm(20.0)), ID1]);
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].len(), 1);
+        assert_eq!(pages[0][0].0, 1);
+        assert!(pack_pieces(&cfg, &[(Mm(200.0), Mm(290.0))]).is_empty());
+    }
+}
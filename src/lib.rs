@@ -0,0 +1,11 @@
+pub mod config_file;
+pub mod configs;
+pub mod extensions;
+pub mod imgprocutils;
+pub mod layout;
+pub mod pdf_doc_ext_idcard;
+pub mod pdf_doc_ext_template;
+pub mod pdf_doc_util;
+pub mod template;
+pub mod text_metrics;
+pub mod validation;
@@ -1,19 +1,22 @@
-mod configs;
-mod extensions;
-mod imgprocutils;
-mod pdf_doc_ext_idcard;
-mod pdf_doc_util;
-
 // use image::DynamicImage;
 // use imageproc::{contrast::stretch_contrast, filter::gaussian_blur_f32};
 
-use crate::{
-    configs::PageMarginConfig, imgprocutils::ImgProcUtils, pdf_doc_ext_idcard::PdfDocIdCardExt,
+use doc_printable::{
+    config_file::FileSettings,
+    configs::{
+        CaptionPosition, CardOrientation, CardProfile, CutGuideKind, DuplexFlip, EdgeInsets,
+        FitMode, GridCentering, GuideExtent, PageMarginConfig, PaperSize, TextAlign, parse_length,
+    },
+    imgprocutils::{HeadPosition, ImgProcUtils},
+    pdf_doc_ext_idcard::{PdfDocIdCardExt, SheetItem},
+    pdf_doc_ext_template::PdfDocTemplateExt,
     pdf_doc_util::PdfDocUtil,
+    template::LayoutTemplate,
 };
 
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{Parser, ValueEnum, builder::RangedU64ValueParser};
+use printpdf::{Mm, Pt};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 enum ProcOp {
@@ -42,6 +45,135 @@ fn parse_proc_op(param_str: &str) -> Result<ProcOp, String> {
     Err("Unknown color".into())
 }
 
+/// Dash and gap length, `None` for solid lines
+#[derive(Debug, Clone, Copy)]
+struct DashArg(Option<(i64, i64)>);
+
+fn parse_dash(param_str: &str) -> Result<DashArg, String> {
+    if param_str.eq_ignore_ascii_case("solid") {
+        return Ok(DashArg(None));
+    }
+    let (dash, gap) = param_str
+        .split_once(',')
+        .ok_or("Expected `dash,gap` or `solid`.")?;
+    let dash = dash.trim().parse().map_err(|_| "Invalid dash length.")?;
+    let gap = gap.trim().parse().map_err(|_| "Invalid gap length.")?;
+    Ok(DashArg(Some((dash, gap))))
+}
+
+fn parse_hex_color(param_str: &str) -> Result<[f32; 3], String> {
+    let hex = param_str.trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err("Expected a colour like #1a2b3c.".into());
+    }
+    let mut rgb = [0.0; 3];
+    for (idx, channel) in rgb.iter_mut().enumerate() {
+        let value = u8::from_str_radix(&hex[idx * 2..idx * 2 + 2], 16)
+            .map_err(|_| "Invalid hex colour.")?;
+        *channel = value as f32 / 255.0;
+    }
+    Ok(rgb)
+}
+
+/// `X,Y` lengths, either may be negative.
+fn parse_offset(param_str: &str) -> Result<(Mm, Mm), String> {
+    let (x, y) = param_str
+        .split_once(',')
+        .ok_or("Expected an offset as `x,y`, e.g. 1.2mm,-0.5mm.")?;
+    Ok((parse_length(x)?, parse_length(y)?))
+}
+
+/// `X[,Y]` scale factors, one value scales both axes.
+fn parse_scale(param_str: &str) -> Result<(f32, f32), String> {
+    let values = param_str
+        .split(',')
+        .map(|value| value.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "Expected a scale as `x` or `x,y`, e.g. 1.0047.")?;
+    let (x, y) = match values[..] {
+        [both] => (both, both),
+        [x, y] => (x, y),
+        _ => return Err("Expected a scale as `x` or `x,y`.".into()),
+    };
+    if !(x > 0.0 && y > 0.0) {
+        return Err("Scale factors must be positive.".into());
+    }
+    Ok((x, y))
+}
+
+/// A resolution in dots per inch, which must be positive.
+fn parse_dpi(param_str: &str) -> Result<f32, String> {
+    match param_str.trim().parse::<f32>() {
+        Ok(dpi) if dpi > 0.0 && dpi.is_finite() => Ok(dpi),
+        _ => Err(format!("`{}` is not a positive resolution.", param_str)),
+    }
+}
+
+/// A length that must not be negative, see `parse_length` for the accepted units.
+fn parse_size(param_str: &str) -> Result<Mm, String> {
+    let length = parse_length(param_str)?;
+    if length < Mm(0.0) {
+        return Err(format!("`{}` must not be negative.", param_str));
+    }
+    Ok(length)
+}
+
+fn parse_template(param_str: &str) -> Result<LayoutTemplate, String> {
+    LayoutTemplate::load(Path::new(param_str))
+}
+
+/// `PATH:SIZE[:COPIES]`, where SIZE is a card profile name or WIDTHxHEIGHT in mm.
+fn parse_sheet_item(param_str: &str) -> Result<SheetItem, String> {
+    let format_err = || "Expected an item as PATH:SIZE[:COPIES], e.g. cnic.jpg:id1:2.".to_string();
+    let (rest, last) = param_str.rsplit_once(':').ok_or_else(format_err)?;
+    let (path, size, copies) = match last.parse::<usize>() {
+        Ok(copies) => {
+            let (path, size) = rest.rsplit_once(':').ok_or_else(format_err)?;
+            (path, size, copies)
+        }
+        Err(_) => (rest, last, 1),
+    };
+
+    let (width, height) = match CardProfile::from_str(size, true) {
+        Ok(profile) => profile.dimensions(),
+        Err(_) => {
+            let (width, height) = size.split_once('x').ok_or_else(format_err)?;
+            let (width, height) = (parse_length(width)?, parse_length(height)?);
+            if width <= Mm(0.0) || height <= Mm(0.0) {
+                return Err("Item dimensions must be positive.".into());
+            }
+            (width, height)
+        }
+    };
+    Ok(SheetItem {
+        image_path: path.to_string(),
+        width,
+        height,
+        copies,
+    })
+}
+
+fn parse_head_position(param_str: &str) -> Result<HeadPosition, String> {
+    let values = param_str
+        .split(',')
+        .map(|value| value.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "Expected pixel positions like 120,480 or 120,480,300.")?;
+    let (crown_y, chin_y, center_x) = match values[..] {
+        [crown_y, chin_y] => (crown_y, chin_y, None),
+        [crown_y, chin_y, center_x] => (crown_y, chin_y, Some(center_x)),
+        _ => return Err("Expected crown_y,chin_y or crown_y,chin_y,center_x.".into()),
+    };
+    if chin_y <= crown_y {
+        return Err("The chin must be below the crown.".into());
+    }
+    Ok(HeadPosition {
+        crown_y,
+        chin_y,
+        center_x,
+    })
+}
+
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Cli {
@@ -53,10 +185,278 @@ pub struct Cli {
     #[clap(short, long, num_args = 1..)] // Accepts one or more values
     pub titles: Vec<String>,
 
+    /// TrueType/OpenType font for titles and captions, needed for non-Latin scripts such as
+    /// Urdu or Arabic (default: built-in Helvetica)
+    #[arg(long)]
+    font: Option<PathBuf>,
+
+    /// Title font size in points (default: 20)
+    #[arg(long)]
+    title_size: Option<f32>,
+
+    /// Title alignment on the page (default: center)
+    #[arg(long, value_enum)]
+    title_align: Option<TextAlign>,
+
+    /// Wrap titles wider than this (default: the width between the margins)
+    #[arg(long, value_parser = parse_size)]
+    title_width: Option<Mm>,
+
+    /// Caption printed with every copy, `{index}`, `{total}`, `{title}` and `{date}` are
+    /// replaced, e.g. "Copy {index}/{total}"
+    #[arg(long)]
+    caption: Option<String>,
+
+    /// Side of the card the caption goes on (default: below)
+    #[arg(long, value_enum)]
+    caption_position: Option<CaptionPosition>,
+
+    /// Caption font size in points (default: 8)
+    #[arg(long)]
+    caption_size: Option<f32>,
+
+    /// Semi-transparent text drawn diagonally across every copy, `{date}` is replaced, e.g.
+    /// "Copy for ABC Bank account opening only - {date}"
+    #[arg(long)]
+    watermark: Option<String>,
+
+    /// Watermark opacity between 0 and 1 (default: 0.3)
+    #[arg(long)]
+    watermark_opacity: Option<f32>,
+
+    /// Watermark colour as #rrggbb
+    #[arg(long, value_parser = parse_hex_color)]
+    watermark_color: Option<[f32; 3]>,
+
+    /// Watermark angle in degrees counter-clockwise (default: along the card diagonal)
+    #[arg(long, allow_negative_numbers = true)]
+    watermark_angle: Option<f32>,
+
+    /// Watermark font size in points (default: spans the card)
+    #[arg(long)]
+    watermark_size: Option<f32>,
+
+    /// Add a "certified true copy" block below the cards with signature, date and stamp areas,
+    /// optionally with a custom declaration (`{date}` is replaced)
+    #[arg(long, num_args = 0..=1, default_missing_value = "Certified true copy of the original.")]
+    attest: Option<String>,
+
+    /// Signature scan placed on the attestation's signature line
+    #[arg(long, requires = "attest")]
+    attest_signature: Option<PathBuf>,
+
+    /// Side of the attestation's stamp box (default: 30mm)
+    #[arg(long, value_parser = parse_size)]
+    attest_stamp_size: Option<Mm>,
+
     /// Path to the output file
     #[arg(short, long, default_value = "./output.pdf")]
     output_path: PathBuf,
 
+    /// Named profile from the config files (~/.config/doc-printable/config.toml and
+    /// ./doc-printable.toml), other flags override its values
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Document size profile, sets card dimensions, orientation and the cut guide settings the
+    /// document calls for (default: id1, passport-photo for photo sheets)
+    #[arg(long, value_enum)]
+    card: Option<CardProfile>,
+
+    /// Paper size: a3, a4, a5, a6, letter, legal, 4x6 or custom:WIDTHxHEIGHT in mm
+    /// (default: a4, 4x6 for photo sheets)
+    #[arg(long)]
+    paper: Option<PaperSize>,
+
+    /// Turn the paper to landscape orientation
+    #[arg(long)]
+    landscape: bool,
+
+    // Lengths below take a unit (mm, cm, in or pt, e.g. `85.6mm` or `3.37in`), plain numbers
+    // are millimetres
+    /// Page width, overrides --paper
+    #[arg(long, value_parser = parse_size)]
+    page_width: Option<Mm>,
+
+    /// Page height, overrides --paper
+    #[arg(long, value_parser = parse_size)]
+    page_height: Option<Mm>,
+
+    /// Card width, overrides --card
+    #[arg(long, value_parser = parse_size)]
+    card_width: Option<Mm>,
+
+    /// Card height, overrides --card
+    #[arg(long, value_parser = parse_size)]
+    card_height: Option<Mm>,
+
+    /// Space above the first row (default: 10mm)
+    #[arg(long, value_parser = parse_size)]
+    margin_top: Option<Mm>,
+
+    /// Space left of the first column, also kept free on the right (default: 10mm)
+    #[arg(long, value_parser = parse_size)]
+    margin_left: Option<Mm>,
+
+    /// Space kept free at the bottom of the page (default: 10mm)
+    #[arg(long, value_parser = parse_size)]
+    margin_bottom: Option<Mm>,
+
+    /// Gap between columns (default: 18mm)
+    #[arg(long, value_parser = parse_size)]
+    horizontal_spacing: Option<Mm>,
+
+    /// Gap between rows (default: 10mm)
+    #[arg(long, value_parser = parse_size)]
+    vertical_spacing: Option<Mm>,
+
+    /// Number of copies of every image, continued on new pages as needed (default: one full page)
+    #[arg(short = 'n', long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    copies: Option<usize>,
+
+    /// Bleed added around every card, cut guides then sit on the trim line; templates take
+    /// none (default: 0mm)
+    #[arg(long, value_parser = parse_size)]
+    bleed: Option<Mm>,
+
+    /// Safe-zone inset from the trim line (default: 3mm)
+    #[arg(long, value_parser = parse_size)]
+    safe_zone: Option<Mm>,
+
+    /// Outline the safe zone of every card for checking
+    #[arg(long, overrides_with = "no_show_safe_zone")]
+    show_safe_zone: bool,
+
+    /// Leave out the safe zone outlines a config file or profile turns on
+    #[arg(long, overrides_with = "show_safe_zone")]
+    no_show_safe_zone: bool,
+
+    /// How cutting positions are marked (default: from the card profile)
+    #[arg(long, value_enum)]
+    cut_guides: Option<CutGuideKind>,
+
+    /// Dash and gap length of guide lines in points, e.g. `10,5`, or `solid`
+    #[arg(long, value_parser = parse_dash)]
+    guide_dash: Option<DashArg>,
+
+    /// Guide colour as hex, e.g. `#000000`
+    #[arg(long, value_parser = parse_hex_color)]
+    guide_color: Option<[f32; 3]>,
+
+    /// Guide stroke width in points (default: 1 for lines, 0.25 for crop marks)
+    #[arg(long)]
+    guide_width: Option<f32>,
+
+    /// Whether guide lines run across the whole page or only across the card grid
+    /// (default: grid)
+    #[arg(long, value_enum)]
+    guide_extent: Option<GuideExtent>,
+
+    /// Clip the images to rounded corners of this radius (3.18mm for ID-1 cards when given
+    /// without a value)
+    #[arg(long, num_args = 0..=1, default_missing_value = "3.18mm", value_parser = parse_size)]
+    corner_radius: Option<Mm>,
+
+    /// Draw a frame of this width in points along each card's trim line, following the rounded
+    /// corners (0.5 when given without a value)
+    #[arg(long, num_args = 0..=1, default_missing_value = "0.5")]
+    border: Option<f32>,
+
+    /// Draw registration targets in the page corners
+    #[arg(long, overrides_with = "no_registration_marks")]
+    registration_marks: bool,
+
+    /// Leave out the registration targets a config file or profile turns on
+    #[arg(long, overrides_with = "registration_marks")]
+    no_registration_marks: bool,
+
+    /// Strips along the page edges the printer cannot reach, as one length or
+    /// `top,right,bottom,left`, e.g. `4mm` or `4mm,4mm,12mm,4mm` (only checked, not drawn)
+    #[arg(long)]
+    unprintable: Option<EdgeInsets>,
+
+    /// Printer profile from the config files with the device's unprintable strips and its offset
+    /// and scale calibration
+    #[arg(long)]
+    printer: Option<String>,
+
+    /// Shift all page content by `x,y` to correct where the printer puts it, e.g. `1mm,-0.5mm`
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_offset)]
+    printer_offset: Option<(Mm, Mm)>,
+
+    /// Scale all page content around the page centre by `x[,y]` to correct the printer's
+    /// scaling, e.g. 1.0047 when 85.6 mm comes out as 85.2 mm
+    #[arg(long, value_parser = parse_scale)]
+    printer_scale: Option<(f32, f32)>,
+
+    /// Check the layout for overlapping, off-page and unprintable cards and title collisions,
+    /// then exit without writing the PDF (status 1 when problems are found)
+    #[arg(long)]
+    check: bool,
+
+    /// Card orientation on the page, `auto` turns cards 90° when that fits more copies
+    /// (default: from the card profile)
+    #[arg(long, value_enum)]
+    orientation: Option<CardOrientation>,
+
+    /// How images whose proportions differ from the card are fitted into it (default: stretch)
+    #[arg(long, value_enum)]
+    fit: Option<FitMode>,
+
+    /// Scan resolution of the images, used by `--fit actual-size` (default: 300)
+    #[arg(long, value_parser = parse_dpi)]
+    source_dpi: Option<f32>,
+
+    /// Centre the card grid on the page, margins and spacings become minimum gutters
+    /// (default: none, both for photo sheets)
+    #[arg(long, value_enum)]
+    center: Option<GridCentering>,
+
+    /// Print the images as front/back pairs for duplex printing, flipped on the given edge
+    #[arg(long, value_enum)]
+    duplex: Option<DuplexFlip>,
+
+    /// Horizontal back-side correction for the printer's duplex registration (default: 0mm)
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_length)]
+    duplex_offset_x: Option<Mm>,
+
+    /// Vertical back-side correction for the printer's duplex registration (default: 0mm)
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_length)]
+    duplex_offset_y: Option<Mm>,
+
+    /// Tile one portrait photo at its exact size on photo paper, config files still apply on top
+    #[arg(long, conflicts_with_all = ["duplex", "pair"])]
+    photo_sheet: bool,
+
+    /// Head position in the photo as `crown_y,chin_y[,center_x]` in pixels, used to crop the
+    /// photo so the head height meets the profile's tolerance
+    #[arg(long, value_parser = parse_head_position, requires = "photo_sheet")]
+    head: Option<HeadPosition>,
+
+    /// Chin-to-crown height on the printed photo (default: middle of the profile's range)
+    #[arg(long, value_parser = parse_size, requires = "head")]
+    head_height: Option<Mm>,
+
+    /// Gap between the top edge and the crown on the printed photo
+    #[arg(long, default_value = "4mm", value_parser = parse_size, requires = "head")]
+    head_top: Mm,
+
+    /// Pack documents of different sizes together on as few sheets as possible, given as
+    /// PATH:SIZE[:COPIES] where SIZE is a card profile or WIDTHxHEIGHT in mm,
+    /// e.g. `--item cnic.jpg:id1:2 --item student.png:86x54`
+    #[arg(long, value_parser = parse_sheet_item, conflicts_with_all = ["photo_sheet", "duplex", "pair"])]
+    item: Vec<SheetItem>,
+
+    /// Lay the pages out from a TOML or JSON template file instead of the grid; its slots take
+    /// the input images by index
+    #[arg(long, value_parser = parse_template, conflicts_with_all = ["item", "photo_sheet", "duplex", "pair"])]
+    template: Option<LayoutTemplate>,
+
+    /// Print the images as front/back pairs side by side on the same sheet, with a fold guide
+    /// between them
+    #[arg(long, conflicts_with = "duplex")]
+    pair: bool,
+
     /// Image processing Operations
     /// 1) c2s : Crop to Subject
     /// 2) contrast:30  Adjust Contrast
@@ -65,6 +465,12 @@ pub struct Cli {
     image_processing_operation: Vec<ProcOp>,
 }
 
+fn path_to_string(path: &Path) -> String {
+    path.to_str()
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| String::from("Invalid path"))
+}
+
 // fn main() {
 //     let mut pdf = PdfDocUtil::new(PageMarginConfig::default());
 //     pdf.register_image_processor(|img| ImgProcUtils::crop_to_subject(&img).unwrap_or(img));
@@ -97,14 +503,274 @@ fn main() {
     let cli = Cli::parse();
     println!("CLI is {:#?}", cli);
 
-    if cli.input_images.len() == 0 {
+    if cli.input_images.is_empty() && cli.item.is_empty() && !cli.check {
         println!("Must define atleast one image.");
         return;
     }
 
-    let mut pdf = PdfDocUtil::new(PageMarginConfig::default());
+    let settings = match FileSettings::load(cli.profile.as_deref(), cli.printer.as_deref()) {
+        Ok(settings) => settings,
+        Err(err) => {
+            println!("Failed to load config {}", err);
+            return;
+        }
+    };
+    // Operations given on the command line replace the configured list
+    let processing = if cli.image_processing_operation.is_empty() {
+        match settings
+            .processing
+            .iter()
+            .map(|op| parse_proc_op(op))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(processing) => processing,
+            Err(err) => {
+                println!("Invalid processing operation in config: {}", err);
+                return;
+            }
+        }
+    } else {
+        cli.image_processing_operation.clone()
+    };
 
-    for opreation in cli.image_processing_operation {
+    let card = cli.card.unwrap_or(if cli.photo_sheet {
+        CardProfile::PassportPhoto
+    } else {
+        CardProfile::Id1
+    });
+    // The photo sheet preset takes the place of the defaults, the config files still win over it
+    let base = if cli.photo_sheet {
+        PageMarginConfig::photo_sheet(card, cli.paper.unwrap_or(PaperSize::Photo4x6))
+    } else {
+        PageMarginConfig::default()
+    };
+    let mut cfg = match settings.config(base) {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            println!("Failed to load config {}", err);
+            return;
+        }
+    };
+    if let Some(card) = cli.card {
+        cfg.set_card_profile(card);
+    }
+    if cli.paper.is_some() || cli.landscape {
+        let paper = cli
+            .paper
+            .unwrap_or(PaperSize::Custom(cfg.page_width, cfg.page_height));
+        cfg.set_paper(paper, cli.landscape);
+    }
+    if let Some(page_width) = cli.page_width {
+        cfg.page_width = page_width;
+    }
+    if let Some(page_height) = cli.page_height {
+        cfg.page_height = page_height;
+    }
+    if let Some(card_width) = cli.card_width {
+        cfg.card_width = card_width;
+    }
+    if let Some(card_height) = cli.card_height {
+        cfg.card_height = card_height;
+    }
+    if let Some(margin_top) = cli.margin_top {
+        cfg.margin_top = margin_top;
+    }
+    if let Some(margin_left) = cli.margin_left {
+        cfg.margin_left = margin_left;
+    }
+    if let Some(margin_bottom) = cli.margin_bottom {
+        cfg.margin_bottom = margin_bottom;
+    }
+    if let Some(horizontal_spacing) = cli.horizontal_spacing {
+        cfg.horizontal_spacing = horizontal_spacing;
+    }
+    if let Some(vertical_spacing) = cli.vertical_spacing {
+        cfg.vertical_spacing = vertical_spacing;
+    }
+    if let Some(bleed) = cli.bleed {
+        cfg.bleed = bleed;
+    }
+    if let Some(safe_zone) = cli.safe_zone {
+        cfg.safe_zone = safe_zone;
+    }
+    // Whichever of the pair comes last is set, neither keeps the config's choice
+    if cli.show_safe_zone || cli.no_show_safe_zone {
+        cfg.show_safe_zone = cli.show_safe_zone;
+    }
+    if let Some(radius) = cli.corner_radius {
+        cfg.corner_radius = radius;
+    }
+    if let Some(width) = cli.border {
+        cfg.border = Some(Pt(width));
+    }
+    if cli.registration_marks || cli.no_registration_marks {
+        cfg.registration_marks = cli.registration_marks;
+    }
+    if let Some(unprintable) = cli.unprintable {
+        cfg.unprintable = unprintable;
+    }
+    if let Some((x, y)) = cli.printer_offset {
+        (cfg.calibration.offset_x, cfg.calibration.offset_y) = (x, y);
+    }
+    if let Some((x, y)) = cli.printer_scale {
+        (cfg.calibration.scale_x, cfg.calibration.scale_y) = (x, y);
+    }
+    if let Some(size) = cli.title_size {
+        cfg.title.font_size = Pt(size);
+    }
+    if let Some(align) = cli.title_align {
+        cfg.title.align = align;
+    }
+    if let Some(width) = cli.title_width {
+        cfg.title.max_width = Some(width);
+    }
+    if let Some(text) = &cli.watermark {
+        cfg.watermark.text = Some(text.clone());
+    }
+    if let Some(opacity) = cli.watermark_opacity {
+        cfg.watermark.opacity = opacity.clamp(0.0, 1.0);
+    }
+    if let Some(color) = cli.watermark_color {
+        cfg.watermark.color = color;
+    }
+    if let Some(angle) = cli.watermark_angle {
+        cfg.watermark.angle = Some(angle);
+    }
+    if let Some(size) = cli.watermark_size {
+        cfg.watermark.font_size = Some(Pt(size));
+    }
+    if let Some(declaration) = &cli.attest {
+        cfg.attestation.declaration = Some(declaration.clone());
+    }
+    if let Some(size) = cli.attest_stamp_size {
+        cfg.attestation.stamp_size = size;
+    }
+    if let Some(template) = &cli.caption {
+        cfg.caption.template = Some(template.clone());
+    }
+    if let Some(position) = cli.caption_position {
+        cfg.caption.position = position;
+    }
+    if let Some(size) = cli.caption_size {
+        cfg.caption.font_size = Pt(size);
+    }
+    if let Some(centering) = cli.center {
+        cfg.centering = centering;
+    }
+    if let Some(offset) = cli.duplex_offset_x {
+        cfg.duplex_offset_x = offset;
+    }
+    if let Some(offset) = cli.duplex_offset_y {
+        cfg.duplex_offset_y = offset;
+    }
+    if let Some(fit) = cli.fit {
+        cfg.fit = fit;
+    }
+    if let Some(dpi) = cli.source_dpi {
+        cfg.source_dpi = dpi;
+    }
+    if let Some(orientation) = cli.orientation {
+        cfg.card_orientation = orientation;
+    }
+    if let Some(kind) = cli.cut_guides {
+        cfg.cut_guides.kind = kind;
+    }
+    if let Some(DashArg(dash)) = cli.guide_dash {
+        cfg.cut_guides.dash = dash;
+    }
+    if let Some(color) = cli.guide_color {
+        cfg.cut_guides.color = color;
+    }
+    if let Some(width) = cli.guide_width {
+        cfg.cut_guides.width = Some(Pt(width));
+    }
+    if let Some(extent) = cli.guide_extent {
+        cfg.cut_guides.extent = extent;
+    }
+    if let Some(flip) = cli.duplex {
+        cfg.duplex_flip = flip;
+    }
+
+    // Templates bring their own page and slots
+    if cli.template.is_none()
+        && let Err(err) = cfg.check_dimensions()
+    {
+        println!("Invalid layout: {}", err);
+        if cli.check {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let aspect = cfg.card_width.0 / cfg.card_height.0;
+    let card_height = cfg.card_height;
+    let mut pdf = PdfDocUtil::new(cfg);
+    if let Some(font) = &cli.font
+        && let Err(err) = pdf.set_font(&path_to_string(font))
+    {
+        println!("Failed to load font {}", err);
+        return;
+    }
+    if let Some(signature) = &cli.attest_signature
+        && let Err(err) = pdf.set_signature_image(&path_to_string(signature))
+    {
+        println!("Failed to load signature {}", err);
+        return;
+    }
+
+    // Mixed sheets and templates place their own pieces
+    let issues = if let Some(template) = &cli.template {
+        pdf.check_template(template)
+    } else if !cli.item.is_empty() {
+        pdf.check_mixed_sheet(&cli.item, cli.titles.first().map(String::as_str))
+    } else {
+        pdf.check_layout(&cli.titles, cli.duplex.is_some())
+    };
+    for issue in &issues {
+        println!("Warning: {}", issue);
+    }
+    if cli.check {
+        if !issues.is_empty() {
+            std::process::exit(1);
+        }
+        println!("Layout OK");
+        return;
+    }
+
+    // Crop before any other processing, the head position refers to the original pixels
+    if cli.photo_sheet {
+        match cli.head {
+            Some(head) => {
+                let Some(head_height) = cli
+                    .head_height
+                    .or(card.head_height().map(|(min, max)| (min + max) / 2.0))
+                else {
+                    println!("--head-height is required for the {:?} profile.", card);
+                    return;
+                };
+                if let Some((min, max)) = card.head_height()
+                    && (head_height < min || head_height > max)
+                {
+                    println!(
+                        "Warning: head height of {} mm is outside the {}-{} mm required for {:?}.",
+                        head_height.0, min.0, max.0, card
+                    );
+                }
+                let head_ratio = head_height.0 / card_height.0;
+                let top_ratio = cli.head_top.0 / card_height.0;
+                pdf.register_image_processor(move |img| {
+                    println!("Cropping To Head Position");
+                    ImgProcUtils::crop_to_head(&img, aspect, &head, head_ratio, top_ratio)
+                });
+            }
+            None => pdf.register_image_processor(move |img| {
+                println!("Cropping To Photo Aspect Ratio");
+                ImgProcUtils::crop_to_aspect(&img, aspect)
+            }),
+        }
+    }
+
+    for opreation in processing {
         match opreation {
             ProcOp::Crop2Subject => pdf.register_image_processor(|img| {
                 println!("Cropping To Subject");
@@ -121,13 +787,51 @@ fn main() {
         }
     }
 
-    for (idx, path) in cli.input_images.iter().enumerate() {
-        let path_string = path
-            .to_str()
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| String::from("Invalid path"));
-        let title = cli.titles.get(idx);
-        pdf.add_card_side(&path_string, title.cloned());
+    if let Some(template) = &cli.template {
+        let paths: Vec<String> = cli.input_images.iter().map(|p| path_to_string(p)).collect();
+        if let Err(err) = pdf.add_template(template, &paths, cli.titles.first().cloned()) {
+            println!("{}", err);
+            return;
+        }
+    } else if !cli.item.is_empty() {
+        pdf.add_mixed_sheet(&cli.item, cli.titles.first().cloned());
+    } else if cli.photo_sheet {
+        for path in &cli.input_images {
+            pdf.add_card_side(&path_to_string(path), None, cli.copies);
+        }
+    } else if cli.pair {
+        for (idx, pair) in cli.input_images.chunks(2).enumerate() {
+            let title = cli.titles.get(idx).cloned();
+            match pair {
+                [front, back] => pdf.add_card_pair(
+                    &path_to_string(front),
+                    &path_to_string(back),
+                    title,
+                    cli.copies,
+                ),
+                [front] => pdf.add_card_side(&path_to_string(front), title, cli.copies),
+                _ => unreachable!(),
+            }
+        }
+    } else if cli.duplex.is_some() {
+        for (idx, pair) in cli.input_images.chunks(2).enumerate() {
+            let front_title = cli.titles.get(idx * 2).cloned();
+            match pair {
+                [front, back] => pdf.add_card_duplex(
+                    &path_to_string(front),
+                    &path_to_string(back),
+                    (front_title, cli.titles.get(idx * 2 + 1).cloned()),
+                    cli.copies,
+                ),
+                [front] => pdf.add_card_side(&path_to_string(front), front_title, cli.copies),
+                _ => unreachable!(),
+            }
+        }
+    } else {
+        for (idx, path) in cli.input_images.iter().enumerate() {
+            let title = cli.titles.get(idx);
+            pdf.add_card_side(&path_to_string(path), title.cloned(), cli.copies);
+        }
     }
 
     pdf.save_pdf(
@@ -1,160 +1,1472 @@
-use crate::pdf_doc_util::{PdfDocUtil, calc_avg_dpi};
+use crate::{
+    configs::{CaptionPosition, CutGuideKind, FitMode, GuideExtent, PageMarginConfig, TextAlign},
+    layout::{SlotRect, cut_positions, duplex_flips_rows, mirror_for_duplex, pack_pieces},
+    pdf_doc_util::PdfDocUtil,
+    text_metrics::wrap_text,
+    validation::{IssueKind, LayoutIssue, check_slots},
+};
 
 use printpdf::{
-    BuiltinFont, Color, Line, LineDashPattern, LinePoint, Mm, Op, Point, Pt, Rgb, TextItem,
-    XObjectTransform,
+    Color, DateTime, ExtendedGraphicsState, Line, LineDashPattern, LinePoint, Mm, Op, PaintMode,
+    Point, Polygon, PolygonRing, Pt, Px, RawImage, Rgb, TextMatrix, WindingOrder, XObjectId,
+    XObjectRotation, XObjectTransform,
 };
 
-pub(crate) trait PdfDocIdCardExt {
-    fn add_card_side(&mut self, image_path: &String, text: Option<String>);
+// Dash pattern in points, finer than the default cut guides so folds are not mistaken for cuts
+const FOLD_DASH: i64 = 2;
+const FOLD_GAP: i64 = 3;
+
+// Crop marks start this far outside the trim line (plus bleed) and are at most this long
+const CROP_MARK_OFFSET: Mm = Mm(1.0);
+const CROP_MARK_LENGTH: Mm = Mm(4.0);
+
+/// Where a page's title and attestation go: hanging from `top` and kept above `floor`.
+#[derive(Debug, Clone, Copy)]
+struct FooterBand {
+    top: Mm,
+    floor: Mm,
+    /// On a duplex back turned over its top edge the band lies above the cards
+    above_cards: bool,
 }
 
-impl PdfDocIdCardExt for PdfDocUtil {
-    fn add_card_side(&mut self, image_path: &String, text: Option<String>) {
-        let mut current_x = self.cfg.margin_left;
-        let mut current_y = self.cfg.page_height - self.cfg.margin_top - self.cfg.card_height;
+/// How the lines of the cut guides follow the pieces on a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GuideShape {
+    /// Along the shared rows and columns of a grid
+    Grid,
+    /// Around every piece on its own, half a gap outside it or on the trim line with a bleed
+    Outlines,
+    /// On the edge of every piece, for slots that are not spaced by the grid settings
+    TrimLines,
+}
 
-        let mut ops: Vec<Op> = Vec::new();
+// First title baseline below the lowest card
+const TITLE_OFFSET: Mm = Mm(20.0);
+
+// Attestation layout: room to sign above the signature line, and between the two lines
+const ATTESTATION_GAP: Mm = Mm(5.0);
+const SIGNATURE_SPACE: Mm = Mm(15.0);
+const SIGNATURE_LINE_LENGTH: Mm = Mm(70.0);
+const DATE_SPACE: Mm = Mm(10.0);
+
+const REGISTRATION_MARK_INSET: Mm = Mm(6.0);
+const REGISTRATION_MARK_RADIUS: Mm = Mm(2.0);
+
+/// One document for a mixed sheet.
+#[derive(Debug, Clone)]
+pub struct SheetItem {
+    pub image_path: String,
+    pub width: Mm,
+    pub height: Mm,
+    pub copies: usize,
+}
+
+pub trait PdfDocIdCardExt {
+    /// Places `copies` copies of the image, starting a new page whenever the layout runs out of
+    /// slots. `None` fills exactly one page.
+    fn add_card_side(&mut self, image_path: &str, text: Option<String>, copies: Option<usize>);
+
+    /// Alternates front and back pages for duplex printing. The back slots are mirrored according
+    /// to `duplex_flip` so every back lands behind its front.
+    fn add_card_duplex(
+        &mut self,
+        front_path: &str,
+        back_path: &str,
+        texts: (Option<String>, Option<String>),
+        copies: Option<usize>,
+    );
+
+    /// Places front and back next to each other in the same row with a fold guide between them,
+    /// for fold-over copies. `copies` counts pairs, `None` fills exactly one page.
+    fn add_card_pair(
+        &mut self,
+        front_path: &str,
+        back_path: &str,
+        text: Option<String>,
+        copies: Option<usize>,
+    );
+
+    /// Packs documents of different sizes together on as few pages as possible, each cut out
+    /// along its own outline.
+    fn add_mixed_sheet(&mut self, items: &[SheetItem], text: Option<String>);
+
+    /// Lays out a page of cards without rendering it and reports overlapping, off-page and
+    /// unprintable slots, and titles that run into the cards or the bottom margin. `duplex`
+    /// checks the mirrored back slots too, with every second title on the back.
+    fn check_layout(&self, titles: &[String], duplex: bool) -> Vec<LayoutIssue>;
+
+    /// Packs a mixed sheet without rendering it and reports copies left out for lack of room,
+    /// then checks every page the way `check_layout` does.
+    fn check_mixed_sheet(&self, items: &[SheetItem], title: Option<&str>) -> Vec<LayoutIssue>;
+}
+
+impl PdfDocIdCardExt for PdfDocUtil {
+    fn add_card_side(&mut self, image_path: &str, text: Option<String>, copies: Option<usize>) {
+        let slots = self.layout.page_slots(
+            &self.grid_cfg(&[text.as_deref()]),
+            self.cfg.card_width,
+            self.cfg.card_height,
+        );
+        if slots.is_empty() {
+            println!("Card does not fit on the page, skipping {}", image_path);
+            return;
+        }
+        let total = copies.unwrap_or(slots.len());
+        let captions = self.captions(total, text.as_deref());
 
         let image = self.load_and_process_image(image_path);
         let image_id = self.document.add_image(&image);
 
-        for idx in 1..=8 {
-            // --- Placement Logic ---
-            if current_x + self.cfg.card_width > self.cfg.page_width - self.cfg.margin_left {
-                current_x = self.cfg.margin_left;
-                current_y -= self.cfg.card_height + self.cfg.vertical_spacing;
+        let mut placed = 0;
+        while placed < total {
+            let page_slots = &slots[..(total - placed).min(slots.len())];
+            let page_captions = captions
+                .get(placed..placed + page_slots.len())
+                .unwrap_or(&[]);
+            placed += page_slots.len();
+            let footer = self.grid_footer(page_slots);
+            self.add_card_page(
+                &image,
+                &image_id,
+                page_slots,
+                page_captions,
+                (text.as_deref(), footer),
+            );
+        }
+    }
+
+    fn add_card_duplex(
+        &mut self,
+        front_path: &str,
+        back_path: &str,
+        texts: (Option<String>, Option<String>),
+        copies: Option<usize>,
+    ) {
+        let front_slots = self.layout.page_slots(
+            &self.grid_cfg(&[texts.0.as_deref(), texts.1.as_deref()]),
+            self.cfg.card_width,
+            self.cfg.card_height,
+        );
+        if front_slots.is_empty() {
+            println!("Card does not fit on the page, skipping {}", front_path);
+            return;
+        }
+        let back_slots = mirror_for_duplex(&front_slots, &self.cfg);
+        let total = copies.unwrap_or(front_slots.len());
+        let front_captions = self.captions(total, texts.0.as_deref());
+        let back_captions = self.captions(total, texts.1.as_deref());
+
+        let front = self.load_and_process_image(front_path);
+        let front_id = self.document.add_image(&front);
+        let back = self.load_and_process_image(back_path);
+        let back_id = self.document.add_image(&back);
+
+        let mut placed = 0;
+        while placed < total {
+            let count = (total - placed).min(front_slots.len());
+            let (front_slots, back_slots) = (&front_slots[..count], &back_slots[..count]);
+            let captions = placed..placed + count;
+            let page_front = front_captions.get(captions.clone()).unwrap_or(&[]);
+            let page_back = back_captions.get(captions).unwrap_or(&[]);
+            placed += count;
+            let front_footer = self.grid_footer(front_slots);
+            self.add_card_page(
+                &front,
+                &front_id,
+                front_slots,
+                page_front,
+                (texts.0.as_deref(), front_footer),
+            );
+            let back_footer = self.duplex_back_footer(back_slots);
+            self.add_card_page(
+                &back,
+                &back_id,
+                back_slots,
+                page_back,
+                (texts.1.as_deref(), back_footer),
+            );
+        }
+    }
+
+    fn add_card_pair(
+        &mut self,
+        front_path: &str,
+        back_path: &str,
+        text: Option<String>,
+        copies: Option<usize>,
+    ) {
+        let slots = self.layout.page_slots(
+            &self.grid_cfg(&[text.as_deref()]),
+            self.cfg.card_width,
+            self.cfg.card_height,
+        );
+        let pairs = pair_slots(&slots);
+        if pairs.is_empty() {
+            println!(
+                "Card pair does not fit on the page, skipping {}",
+                front_path
+            );
+            return;
+        }
+        let total = copies.unwrap_or(pairs.len());
+        let captions = self.captions(total, text.as_deref());
+
+        let front = self.load_and_process_image(front_path);
+        let front_id = self.document.add_image(&front);
+        let back = self.load_and_process_image(back_path);
+        let back_id = self.document.add_image(&back);
+
+        let mut placed = 0;
+        while placed < total {
+            let page_pairs = &pairs[..(total - placed).min(pairs.len())];
+            let page_captions = captions
+                .get(placed..placed + page_pairs.len())
+                .unwrap_or(&[]);
+            placed += page_pairs.len();
+            self.add_card_pair_page(
+                (&front, &front_id),
+                (&back, &back_id),
+                page_pairs,
+                page_captions,
+                text.as_deref(),
+            );
+        }
+    }
+
+    fn add_mixed_sheet(&mut self, items: &[SheetItem], text: Option<String>) {
+        let (owners, sizes) = sheet_pieces(items);
+        let pages = pack_pieces(&self.grid_cfg(&[text.as_deref()]), &sizes);
+        let placed: usize = pages.iter().map(Vec::len).sum();
+        if placed < sizes.len() {
+            println!(
+                "{} copies do not fit on the page and were skipped",
+                sizes.len() - placed
+            );
+        }
+
+        let images: Vec<(RawImage, XObjectId)> = items
+            .iter()
+            .map(|item| {
+                let image = self.load_and_process_image(&item.image_path);
+                let image_id = self.document.add_image(&image);
+                (image, image_id)
+            })
+            .collect();
+
+        for page in pages {
+            let slots: Vec<SlotRect> = page.iter().map(|(_, slot)| *slot).collect();
+            // Mixed sheets have no captions
+            let footer = self.footer_below(bottom_of(&slots, self.first_row_y()));
+
+            let mut ops: Vec<Op> = page
+                .iter()
+                .flat_map(|&(piece, slot)| {
+                    let (image, image_id) = &images[owners[piece]];
+                    self.card_image_ops(image, image_id, &slot)
+                })
+                .collect();
+            ops.append(&mut self.watermark_ops(&slots));
+            ops.append(&mut self.border_ops(&slots));
+            ops.append(&mut self.cut_guide_ops(&slots, GuideShape::Outlines));
+            ops.append(&mut self.safe_zone_ops(&slots));
+            ops.append(&mut self.registration_mark_ops());
+            ops.append(&mut self.footer_ops(text.as_deref(), footer));
+
+            self.add_page_to_document(ops);
+        }
+    }
+
+    fn check_mixed_sheet(&self, items: &[SheetItem], title: Option<&str>) -> Vec<LayoutIssue> {
+        let (_, sizes) = sheet_pieces(items);
+        let pages = pack_pieces(&self.grid_cfg(&[title]), &sizes);
+        let mut issues = Vec::new();
+        let placed: usize = pages.iter().map(Vec::len).sum();
+        if placed < sizes.len() {
+            issues.push(LayoutIssue::new(
+                IssueKind::NoFit,
+                format!(
+                    "{} copies do not fit on the page and are left out",
+                    sizes.len() - placed
+                ),
+                &[
+                    "item",
+                    "page_width",
+                    "page_height",
+                    "margin_top",
+                    "margin_left",
+                    "margin_bottom",
+                ],
+            ));
+        }
+
+        for (idx, page) in pages.iter().enumerate() {
+            let slots: Vec<SlotRect> = page.iter().map(|(_, slot)| *slot).collect();
+            issues.append(&mut check_slots(
+                &self.cfg,
+                &slots,
+                &format!("mixed sheet's page {}", idx + 1),
+            ));
+            let footer = self.footer_below(bottom_of(&slots, self.first_row_y()));
+            if let Some(title) = title {
+                issues.append(&mut self.check_title(title, footer));
             }
+            issues.extend(self.check_attestation(title, footer));
+        }
+        issues
+    }
 
-            if current_y < self.cfg.margin_bottom {
-                self.add_page_to_document(std::mem::take(&mut ops));
-                println!("ops len after mem::take {}", ops.len());
-                current_y = self.cfg.page_height - self.cfg.margin_top - self.cfg.card_height;
+    fn check_layout(&self, titles: &[String], duplex: bool) -> Vec<LayoutIssue> {
+        let texts: Vec<Option<&str>> = if titles.is_empty() {
+            vec![None]
+        } else {
+            titles.iter().map(|title| Some(title.as_str())).collect()
+        };
+        let slots = self.layout.page_slots(
+            &self.grid_cfg(&texts),
+            self.cfg.card_width,
+            self.cfg.card_height,
+        );
+        if slots.is_empty() {
+            let mut issue = LayoutIssue::new(
+                IssueKind::NoFit,
+                format!(
+                    "A {} x {} mm card does not fit on the {} x {} mm page",
+                    self.cfg.card_width.0,
+                    self.cfg.card_height.0,
+                    self.cfg.page_width.0,
+                    self.cfg.page_height.0
+                ),
+                &[
+                    "card_width",
+                    "card_height",
+                    "page_width",
+                    "page_height",
+                    "margin_top",
+                    "margin_left",
+                    "margin_bottom",
+                ],
+            );
+            // The attestation block takes its room from the grid
+            if self.cfg.attestation.declaration.is_some() {
+                issue
+                    .fields
+                    .extend(["attestation.stamp_size", "attestation.font_size"]);
             }
+            return vec![issue];
+        }
 
-            let avg_dpi = calc_avg_dpi(&self.cfg.card_width, &self.cfg.card_height, &image);
-
-            ops.push(Op::UseXobject {
-                id: image_id.clone(),
-                transform: XObjectTransform {
-                    translate_x: Some(current_x.into_pt()),
-                    translate_y: Some(current_y.into_pt()),
-                    rotate: None,
-                    scale_x: Some(1.0),
-                    scale_y: Some(1.0),
-                    dpi: Some(avg_dpi),
-                },
-            });
+        let mut issues = check_slots(&self.cfg, &slots, "front");
+        let front_footer = self.grid_footer(&slots);
+        let mut footers = vec![front_footer];
+        let mut texts = texts;
+        if duplex {
+            let back_slots = mirror_for_duplex(&slots, &self.cfg);
+            issues.append(&mut check_slots(&self.cfg, &back_slots, "back"));
+            // Duplex titles alternate between the fronts and the backs
+            footers.push(self.duplex_back_footer(&back_slots));
+            if texts.len() % 2 == 1 {
+                texts.push(None);
+            }
+        }
+        for (text, footer) in texts.into_iter().zip(footers.into_iter().cycle()) {
+            if let Some(title) = text {
+                issues.append(&mut self.check_title(title, footer));
+            }
+            issues.extend(self.check_attestation(text, footer));
+        }
+        issues
+    }
+}
+
+/// Groups neighbouring slots of the same row into (front, back) pairs, a leftover slot at the end
+/// of a row stays empty.
+fn pair_slots(slots: &[SlotRect]) -> Vec<(SlotRect, SlotRect)> {
+    let mut pairs = Vec::new();
+    let mut iter = slots.iter().peekable();
+    while let Some(front) = iter.next() {
+        if let Some(back) = iter.next_if(|back| back.y == front.y) {
+            pairs.push((*front, *back));
+        }
+    }
+    pairs
+}
+
+/// Draws the image into `slot` according to `fit`, clipped to `clip` when it overhangs the slot.
+fn image_ops(
+    image: &RawImage,
+    image_id: &XObjectId,
+    (slot, clip): (&SlotRect, &SlotRect),
+    fit: FitMode,
+    source_dpi: f32,
+) -> Vec<Op> {
+    // Work in the card's own orientation, a rotated card lies across its slot
+    let (card_width, card_height) = if slot.is_rotated() {
+        (slot.height, slot.width)
+    } else {
+        (slot.width, slot.height)
+    };
+    let (image_width, image_height) = (image.width.max(1) as f32, image.height.max(1) as f32);
+    let (width, height) = match fit {
+        FitMode::Stretch => (card_width, card_height),
+        FitMode::Contain | FitMode::Cover => {
+            let (scale_x, scale_y) = (card_width.0 / image_width, card_height.0 / image_height);
+            let scale = if fit == FitMode::Contain {
+                scale_x.min(scale_y)
+            } else {
+                scale_x.max(scale_y)
+            };
+            (Mm(image_width * scale), Mm(image_height * scale))
+        }
+        FitMode::ActualSize => (
+            Mm(image_width / source_dpi * 25.4),
+            Mm(image_height / source_dpi * 25.4),
+        ),
+    };
+    let (offset_x, offset_y) = ((card_width - width) / 2.0, (card_height - height) / 2.0);
+
+    // Turning around the bottom left corner swings the card to the left of its origin, so at 90°
+    // the card's x runs up the slot and its y leftwards from the slot's right edge, and at 270°
+    // its x runs down the slot and its y rightwards from the slot's left edge
+    let (translate_x, translate_y) = match slot.rotation {
+        90 => (slot.right() - offset_y, slot.y + offset_x),
+        270 => (slot.x + offset_y, slot.top() - offset_x),
+        _ => (slot.x + offset_x, slot.y + offset_y),
+    };
+    let rotate = (slot.rotation != 0).then_some(XObjectRotation {
+        angle_ccw_degrees: slot.rotation as f32,
+        rotation_center_x: Px(0),
+        rotation_center_y: Px(0),
+    });
+    let draw = Op::UseXobject {
+        id: image_id.clone(),
+        transform: XObjectTransform {
+            translate_x: Some(translate_x.into_pt()),
+            translate_y: Some(translate_y.into_pt()),
+            rotate,
+            // At 72 dpi one pixel is one point, the scales take it from there to the exact size
+            scale_x: Some(width.into_pt().0 / image_width),
+            scale_y: Some(height.into_pt().0 / image_height),
+            dpi: Some(72.0),
+        },
+    };
+
+    // Contain lands on the slot edges up to rounding, which needs no clip
+    let overhangs = width.0 - card_width.0 > 0.01 || height.0 - card_height.0 > 0.01;
+    if !overhangs {
+        return vec![draw];
+    }
+    vec![
+        Op::SaveGraphicsState,
+        rect_op(clip, PaintMode::Clip),
+        draw,
+        Op::RestoreGraphicsState,
+    ]
+}
+
+pub(crate) fn rgb_color([r, g, b]: [f32; 3]) -> Color {
+    Color::Rgb(Rgb {
+        r,
+        g,
+        b,
+        icc_profile: None,
+    })
+}
+
+pub(crate) fn line_dash_op(dash: i64, gap: i64) -> Op {
+    Op::SetLineDashPattern {
+        dash: LineDashPattern {
+            offset: 0,
+            dash_1: Some(dash),
+            gap_1: Some(gap),
+            dash_2: None,
+            gap_2: None,
+            dash_3: None,
+            gap_3: None,
+        },
+    }
+}
+
+/// The piece sizes of a mixed sheet, one per copy, with the index of the item each came from.
+fn sheet_pieces(items: &[SheetItem]) -> (Vec<usize>, Vec<(Mm, Mm)>) {
+    let owners: Vec<usize> = items
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, item)| std::iter::repeat_n(idx, item.copies))
+        .collect();
+    let sizes = owners
+        .iter()
+        .map(|&idx| (items[idx].width, items[idx].height))
+        .collect();
+    (owners, sizes)
+}
 
-            if idx % 2 == 0 {
-                ops.append(&mut vec![
-                    Op::SetOutlineColor {
-                        col: Color::Rgb(Rgb {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            icc_profile: None,
-                        }),
+/// Black dashes for fold lines, set apart from the cut guides.
+pub(crate) fn fold_style_ops() -> Vec<Op> {
+    vec![
+        Op::SetOutlineColor {
+            col: Color::Rgb(Rgb {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+                icc_profile: None,
+            }),
+        },
+        line_dash_op(FOLD_DASH, FOLD_GAP),
+    ]
+}
+
+fn line_op(from: (Mm, Mm), to: (Mm, Mm)) -> Op {
+    Op::DrawLine {
+        line: Line {
+            points: vec![
+                LinePoint {
+                    p: Point {
+                        x: from.0.into_pt(),
+                        y: from.1.into_pt(),
+                    },
+                    bezier: false,
+                },
+                LinePoint {
+                    p: Point {
+                        x: to.0.into_pt(),
+                        y: to.1.into_pt(),
                     },
-                    Op::SetLineDashPattern {
-                        dash: LineDashPattern {
-                            offset: 0,
-                            dash_1: Some(10),
-                            gap_1: Some(5),
-                            dash_2: None,
-                            gap_2: None,
-                            dash_3: None,
-                            gap_3: None,
+                    bezier: false,
+                },
+            ],
+            is_closed: false,
+        },
+    }
+}
+
+/// Fills the placeholders of a caption template for the copy numbered `index` (from 1).
+pub(crate) fn caption_text(
+    template: &str,
+    index: usize,
+    total: usize,
+    title: Option<&str>,
+) -> String {
+    template
+        .replace("{index}", &index.to_string())
+        .replace("{total}", &total.to_string())
+        .replace("{title}", title.unwrap_or_default())
+        .replace("{date}", &today())
+}
+
+/// The current date as YYYY-MM-DD.
+fn today() -> String {
+    let date = DateTime::now().date;
+    format!("{:04}-{:02}-{:02}", date.year, date.month, date.day)
+}
+
+/// Outline of a rectangle, for stroking or clipping.
+fn rect_op(rect: &SlotRect, mode: PaintMode) -> Op {
+    rounded_rect_op(rect, Mm(0.0), mode)
+}
+
+/// Outline of a rectangle with quarter-circle corners of `radius`, square when it is zero.
+fn rounded_rect_op(rect: &SlotRect, radius: Mm, mode: PaintMode) -> Op {
+    let radius = radius.min(rect.width / 2.0).min(rect.height / 2.0);
+    let (left, bottom, right, top) = (rect.x, rect.y, rect.right(), rect.top());
+    let points = if radius > Mm(0.0) {
+        // Distance of the bezier handles for a quarter circle
+        let k = radius * 0.552_284_8;
+        let (r, h) = (radius, radius - k);
+        vec![
+            ((left + r, bottom), false),
+            ((right - r, bottom), false),
+            ((right - h, bottom), true),
+            ((right, bottom + h), true),
+            ((right, bottom + r), false),
+            ((right, top - r), false),
+            ((right, top - h), true),
+            ((right - h, top), true),
+            ((right - r, top), false),
+            ((left + r, top), false),
+            ((left + h, top), true),
+            ((left, top - h), true),
+            ((left, top - r), false),
+            ((left, bottom + r), false),
+            ((left, bottom + h), true),
+            ((left + h, bottom), true),
+            ((left + r, bottom), false),
+        ]
+    } else {
+        vec![
+            ((left, bottom), false),
+            ((right, bottom), false),
+            ((right, top), false),
+            ((left, top), false),
+        ]
+    };
+
+    Op::DrawPolygon {
+        polygon: Polygon {
+            rings: vec![PolygonRing {
+                points: points
+                    .into_iter()
+                    .map(|((x, y), bezier)| LinePoint {
+                        p: Point {
+                            x: x.into_pt(),
+                            y: y.into_pt(),
                         },
+                        bezier,
+                    })
+                    .collect(),
+            }],
+            mode,
+            winding_order: WindingOrder::NonZero,
+        },
+    }
+}
+
+/// Lowest slot edge on the page, used to position everything below the grid.
+fn bottom_of(slots: &[SlotRect], fallback: Mm) -> Mm {
+    slots.iter().map(|slot| slot.y).fold(fallback, Mm::min)
+}
+
+impl PdfDocUtil {
+    /// Renders one page of copies together with its own cut guides and title. `captions` go
+    /// with the slots of the same index, the title and attestation into `footer`.
+    fn add_card_page(
+        &mut self,
+        image: &RawImage,
+        image_id: &XObjectId,
+        slots: &[SlotRect],
+        captions: &[String],
+        (text, footer): (Option<&str>, FooterBand),
+    ) {
+        let mut ops: Vec<Op> = slots
+            .iter()
+            .flat_map(|slot| self.card_image_ops(image, image_id, slot))
+            .collect();
+        ops.append(&mut self.watermark_ops(slots));
+        ops.append(&mut self.border_ops(slots));
+        ops.append(&mut self.cut_guide_ops(slots, GuideShape::Grid));
+        ops.append(&mut self.safe_zone_ops(slots));
+        ops.append(&mut self.registration_mark_ops());
+        for (slot, caption) in slots.iter().zip(captions) {
+            ops.append(&mut self.caption_ops(caption, slot));
+        }
+        ops.append(&mut self.footer_ops(text, footer));
+
+        self.add_page_to_document(ops);
+    }
+
+    /// Renders one page of front/back pairs with fold guides between the two halves of a pair.
+    /// `captions` go under or over the pair of the same index.
+    fn add_card_pair_page(
+        &mut self,
+        front: (&RawImage, &XObjectId),
+        back: (&RawImage, &XObjectId),
+        pairs: &[(SlotRect, SlotRect)],
+        captions: &[String],
+        text: Option<&str>,
+    ) {
+        let slots: Vec<SlotRect> = pairs.iter().flat_map(|(f, b)| [*f, *b]).collect();
+        let footer = self.grid_footer(&slots);
+
+        let mut ops: Vec<Op> = Vec::new();
+        for (front_slot, back_slot) in pairs {
+            ops.append(&mut self.card_image_ops(front.0, front.1, front_slot));
+            ops.append(&mut self.card_image_ops(back.0, back.1, back_slot));
+        }
+        ops.append(&mut self.watermark_ops(&slots));
+        ops.append(&mut self.border_ops(&slots));
+        // The pair is cut out as one piece, the fold sits inside it
+        let outlines: Vec<SlotRect> = pairs
+            .iter()
+            .map(|(front_slot, back_slot)| SlotRect {
+                width: back_slot.right() - front_slot.x,
+                ..*front_slot
+            })
+            .collect();
+        ops.append(&mut self.cut_guide_ops(&outlines, GuideShape::Grid));
+
+        ops.push(Op::SaveGraphicsState);
+        ops.append(&mut fold_style_ops());
+        for (front_slot, back_slot) in pairs {
+            let x = (front_slot.right() + back_slot.x) / 2.0;
+            ops.push(line_op((x, front_slot.top()), (x, front_slot.y)));
+        }
+        ops.push(Op::RestoreGraphicsState);
+        ops.append(&mut self.safe_zone_ops(&slots));
+        ops.append(&mut self.registration_mark_ops());
+        for (outline, caption) in outlines.iter().zip(captions) {
+            ops.append(&mut self.caption_ops(caption, outline));
+        }
+
+        ops.append(&mut self.footer_ops(text, footer));
+
+        self.add_page_to_document(ops);
+    }
+
+    /// Where `title_ops` puts the title in `footer`, compared with the cards and the page.
+    fn check_title(&self, title: &str, footer: FooterBand) -> Vec<LayoutIssue> {
+        let size = Mm::from(self.cfg.title.font_size);
+        let first_baseline = footer.top - TITLE_OFFSET;
+        // Roughly the ascenders of the first line and the descenders of the last
+        let title_top = first_baseline + size * 0.75;
+        let title_bottom = footer.top - self.title_depth(Some(title));
+
+        let mut issues = Vec::new();
+        if footer.above_cards {
+            if title_bottom < footer.floor {
+                issues.push(LayoutIssue::new(
+                    IssueKind::TitleCollision,
+                    format!(
+                        "Title \"{}\" on the back reaches {:.1} mm into the first row of cards",
+                        title,
+                        (footer.floor - title_bottom).0
+                    ),
+                    &["title.font_size", "title.max_width", "margin_bottom"],
+                ));
+            }
+            return issues;
+        }
+
+        let (below, _) = self.cfg.caption_bands();
+        // The caption band already includes the bleed
+        let cards_bottom = if below > Mm(0.0) {
+            footer.top
+        } else {
+            footer.top - self.cfg.bleed
+        };
+        // Against the paper edge as the calibrated printer puts it
+        let (_, device_bottom) = self.cfg.device_point(Mm(0.0), title_bottom);
+        if title_top > cards_bottom + Mm(0.01) {
+            issues.push(LayoutIssue::new(
+                IssueKind::TitleCollision,
+                format!(
+                    "Title \"{}\" reaches {:.1} mm into the last row of cards",
+                    title,
+                    (title_top - cards_bottom).0
+                ),
+                &["title.font_size"],
+            ));
+        }
+        let fields = [
+            "title.font_size",
+            "title.max_width",
+            "margin_top",
+            "vertical_spacing",
+        ];
+        if device_bottom < Mm(0.0) {
+            issues.push(LayoutIssue::new(
+                IssueKind::OffPage,
+                format!(
+                    "Title \"{}\" runs {:.1} mm past the bottom page edge",
+                    title, -device_bottom.0
+                ),
+                &fields,
+            ));
+        } else if device_bottom < self.cfg.unprintable.bottom {
+            issues.push(LayoutIssue::new(
+                IssueKind::Unprintable,
+                format!(
+                    "Title \"{}\" reaches {:.1} mm into the unprintable strip along the bottom edge",
+                    title,
+                    (self.cfg.unprintable.bottom - device_bottom).0
+                ),
+                &[&fields[..], &["unprintable"]].concat(),
+            ));
+        } else if title_bottom < footer.floor {
+            issues.push(LayoutIssue::new(
+                IssueKind::TitleCollision,
+                format!(
+                    "Title \"{}\" runs {:.1} mm into the bottom margin",
+                    title,
+                    (footer.floor - title_bottom).0
+                ),
+                &[&fields[..], &["margin_bottom"]].concat(),
+            ));
+        }
+        issues
+    }
+
+    /// A card image fitted to its slot the configured way, with the bleed around it, clipped to
+    /// the card's rounded corners.
+    ///
+    /// The card keeps its true size. A covering image shows what overhangs the card in the bleed,
+    /// and under a stretched or covering card the image scaled evenly over the whole bleed box
+    /// fills the rest with the colours of the card's edges.
+    pub(crate) fn card_image_ops(
+        &self,
+        image: &RawImage,
+        image_id: &XObjectId,
+        slot: &SlotRect,
+    ) -> Vec<Op> {
+        let (fit, source_dpi) = (self.cfg.fit, self.cfg.source_dpi);
+        let bleed_box = slot.outset(self.cfg.bleed);
+        let mut ops = Vec::new();
+        if self.cfg.bleed > Mm(0.0) && matches!(fit, FitMode::Stretch | FitMode::Cover) {
+            ops.append(&mut image_ops(
+                image,
+                image_id,
+                (&bleed_box, &bleed_box),
+                FitMode::Cover,
+                source_dpi,
+            ));
+        }
+        let clip = if fit == FitMode::Cover {
+            &bleed_box
+        } else {
+            slot
+        };
+        ops.append(&mut image_ops(
+            image,
+            image_id,
+            (slot, clip),
+            fit,
+            source_dpi,
+        ));
+        if self.cfg.corner_radius > Mm(0.0) {
+            // The bleed keeps its width around the corners too
+            let radius = self.cfg.corner_radius + self.cfg.bleed;
+            ops.insert(0, Op::SaveGraphicsState);
+            ops.insert(1, rounded_rect_op(&bleed_box, radius, PaintMode::Clip));
+            ops.push(Op::RestoreGraphicsState);
+        }
+        ops
+    }
+
+    /// A frame along every card's trim line, following its rounded corners.
+    pub(crate) fn border_ops(&self, slots: &[SlotRect]) -> Vec<Op> {
+        let Some(width) = self.cfg.border else {
+            return Vec::new();
+        };
+
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetOutlineColor {
+                col: rgb_color([0.0, 0.0, 0.0]),
+            },
+            Op::SetOutlineThickness { pt: width },
+            Op::SetLineDashPattern {
+                dash: LineDashPattern::default(),
+            },
+        ];
+        ops.extend(
+            slots
+                .iter()
+                .map(|slot| rounded_rect_op(slot, self.cfg.corner_radius, PaintMode::Stroke)),
+        );
+        ops.push(Op::RestoreGraphicsState);
+        ops
+    }
+
+    /// The caption of every copy from the first to `total`, empty without a caption template.
+    fn captions(&self, total: usize, title: Option<&str>) -> Vec<String> {
+        match &self.cfg.caption.template {
+            Some(template) => (1..=total)
+                .map(|index| caption_text(template, index, total, title))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn first_row_y(&self) -> Mm {
+        self.cfg.page_height - self.cfg.margin_top - self.cfg.card_height
+    }
+
+    /// Cut guides around the given pieces in the configured style. Guide lines follow the
+    /// pieces as `shape` says, crop marks sit at every corner whatever the shape.
+    pub(crate) fn cut_guide_ops(&self, pieces: &[SlotRect], shape: GuideShape) -> Vec<Op> {
+        let style = &self.cfg.cut_guides;
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetOutlineColor {
+                col: rgb_color(style.color),
+            },
+        ];
+
+        match style.kind {
+            CutGuideKind::None => return Vec::new(),
+            CutGuideKind::Lines => {
+                ops.push(Op::SetOutlineThickness {
+                    pt: style.width.unwrap_or(Pt(1.0)),
+                });
+                ops.push(match style.dash {
+                    Some((dash, gap)) => line_dash_op(dash, gap),
+                    None => Op::SetLineDashPattern {
+                        dash: LineDashPattern::default(),
                     },
-                    Op::DrawLine {
-                        line: Line {
-                            points: vec![
-                                LinePoint {
-                                    p: Point {
-                                        x: Pt(0.0),
-                                        y: (current_y - self.cfg.vertical_spacing / 2.0).into_pt(),
-                                    },
-                                    bezier: false,
-                                },
-                                LinePoint {
+                });
+                ops.append(&mut match shape {
+                    GuideShape::Grid => self.guide_line_ops(pieces),
+                    GuideShape::Outlines => self.outline_ops(pieces, self.outline_offset()),
+                    GuideShape::TrimLines => self.outline_ops(pieces, (Mm(0.0), Mm(0.0))),
+                });
+            }
+            CutGuideKind::CropMarks => {
+                ops.push(Op::SetOutlineThickness {
+                    pt: style.width.unwrap_or(Pt(0.25)),
+                });
+                ops.push(Op::SetLineDashPattern {
+                    dash: LineDashPattern::default(),
+                });
+                let mut marks = self.crop_mark_ops(pieces);
+                if marks.is_empty() && !pieces.is_empty() {
+                    println!("Warning: no room for crop marks around the cards, none are drawn");
+                }
+                ops.append(&mut marks);
+            }
+        }
+
+        ops.push(Op::RestoreGraphicsState);
+        ops
+    }
+
+    /// How far outlines stand off the pieces: half a gap, or nothing when the bleed fills it.
+    fn outline_offset(&self) -> (Mm, Mm) {
+        let (h_spacing, v_spacing) = self.cfg.slot_spacing();
+        if self.cfg.bleed > Mm(0.0) {
+            (Mm(0.0), Mm(0.0))
+        } else {
+            (h_spacing / 2.0, v_spacing / 2.0)
+        }
+    }
+
+    /// A closed line around every piece, `offset` outside it.
+    fn outline_ops(&self, pieces: &[SlotRect], (x_offset, y_offset): (Mm, Mm)) -> Vec<Op> {
+        pieces
+            .iter()
+            .map(|piece| {
+                let outline = SlotRect {
+                    x: piece.x - x_offset,
+                    y: piece.y - y_offset,
+                    width: piece.width + x_offset * 2.0,
+                    height: piece.height + y_offset * 2.0,
+                    ..*piece
+                };
+                rect_op(&outline, PaintMode::Stroke)
+            })
+            .collect()
+    }
+
+    /// Lines along every row and column edge the layout produced, across the whole page or only
+    /// across the grid.
+    fn guide_line_ops(&self, pieces: &[SlotRect]) -> Vec<Op> {
+        let (xs, ys) = cut_positions(pieces, &self.cfg);
+        let (Some(&first_x), Some(&last_x), Some(&first_y), Some(&last_y)) =
+            (xs.first(), xs.last(), ys.first(), ys.last())
+        else {
+            return Vec::new();
+        };
+
+        let ((left, right), (bottom, top)) = match self.cfg.cut_guides.extent {
+            GuideExtent::Page => (
+                (Mm(0.0), self.cfg.page_width),
+                (Mm(0.0), self.cfg.page_height),
+            ),
+            GuideExtent::Grid => ((first_x, last_x), (first_y, last_y)),
+        };
+
+        let horizontal = ys.iter().map(|&y| line_op((left, y), (right, y)));
+        let vertical = xs.iter().map(|&x| line_op((x, top), (x, bottom)));
+        horizontal.chain(vertical).collect()
+    }
+
+    /// Short ticks outside every corner of the slots, kept clear of the bleed. A tick facing
+    /// another slot takes at most half the gap, the neighbour's tick has the other half, and the
+    /// ticks of the outer corners run into the margin up to the page edge.
+    fn crop_mark_ops(&self, slots: &[SlotRect]) -> Vec<Op> {
+        let offset = self.cfg.bleed + CROP_MARK_OFFSET;
+        // Room for a tick leaving `from` at `start` in `dir` along the line at `across`
+        let room = |from: usize, start: Mm, dir: f32, across: Mm, horizontal: bool| -> Mm {
+            let facing = slots
+                .iter()
+                .enumerate()
+                .filter(|&(idx, _)| idx != from)
+                .filter_map(|(_, other)| {
+                    let (near, far, low, high) = if horizontal {
+                        (other.x, other.right(), other.y, other.top())
+                    } else {
+                        (other.y, other.top(), other.x, other.right())
+                    };
+                    if across < low - offset || across > high + offset {
+                        return None;
+                    }
+                    let gap = if dir > 0.0 { near - start } else { start - far };
+                    (gap >= Mm(0.0)).then_some(gap / 2.0)
+                })
+                .reduce(Mm::min);
+            let page_edge = match (horizontal, dir > 0.0) {
+                (true, true) => self.cfg.page_width - start,
+                (false, true) => self.cfg.page_height - start,
+                _ => start,
+            };
+            facing.unwrap_or(page_edge) - offset
+        };
+
+        let mut ops = Vec::new();
+        for (idx, slot) in slots.iter().enumerate() {
+            for (x, x_dir) in [(slot.x, -1.0), (slot.right(), 1.0)] {
+                for (y, y_dir) in [(slot.y, -1.0), (slot.top(), 1.0)] {
+                    let h_length = CROP_MARK_LENGTH.min(room(idx, x, x_dir, y, true));
+                    if h_length > Mm(0.0) {
+                        ops.push(line_op(
+                            (x + offset * x_dir, y),
+                            (x + (offset + h_length) * x_dir, y),
+                        ));
+                    }
+                    let v_length = CROP_MARK_LENGTH.min(room(idx, y, y_dir, x, false));
+                    if v_length > Mm(0.0) {
+                        ops.push(line_op(
+                            (x, y + offset * y_dir),
+                            (x, y + (offset + v_length) * y_dir),
+                        ));
+                    }
+                }
+            }
+        }
+        ops
+    }
+
+    /// Circle-and-cross targets in the page corners for aligning the sheet on a cutter.
+    pub(crate) fn registration_mark_ops(&self) -> Vec<Op> {
+        if !self.cfg.registration_marks {
+            return Vec::new();
+        }
+
+        let inset = REGISTRATION_MARK_INSET;
+        let radius = REGISTRATION_MARK_RADIUS;
+        let arm = radius * 1.5;
+        // Distance of the bezier handles for a quarter circle
+        let k = radius * 0.552_284_8;
+
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetOutlineColor {
+                col: Color::Rgb(Rgb {
+                    r: 0.0,
+                    g: 0.0,
+                    b: 0.0,
+                    icc_profile: None,
+                }),
+            },
+            Op::SetOutlineThickness { pt: Pt(0.25) },
+            Op::SetLineDashPattern {
+                dash: LineDashPattern::default(),
+            },
+        ];
+        for cx in [inset, self.cfg.page_width - inset] {
+            for cy in [inset, self.cfg.page_height - inset] {
+                ops.push(line_op((cx - arm, cy), (cx + arm, cy)));
+                ops.push(line_op((cx, cy - arm), (cx, cy + arm)));
+
+                let circle = [
+                    ((cx + radius, cy), false),
+                    ((cx + radius, cy + k), true),
+                    ((cx + k, cy + radius), true),
+                    ((cx, cy + radius), false),
+                    ((cx - k, cy + radius), true),
+                    ((cx - radius, cy + k), true),
+                    ((cx - radius, cy), false),
+                    ((cx - radius, cy - k), true),
+                    ((cx - k, cy - radius), true),
+                    ((cx, cy - radius), false),
+                    ((cx + k, cy - radius), true),
+                    ((cx + radius, cy - k), true),
+                    ((cx + radius, cy), false),
+                ];
+                ops.push(Op::DrawPolygon {
+                    polygon: Polygon {
+                        rings: vec![PolygonRing {
+                            points: circle
+                                .into_iter()
+                                .map(|((x, y), bezier)| LinePoint {
                                     p: Point {
-                                        x: self.cfg.page_width.into_pt(),
-                                        y: (current_y - self.cfg.vertical_spacing / 2.0).into_pt(),
+                                        x: x.into_pt(),
+                                        y: y.into_pt(),
                                     },
-                                    bezier: false,
-                                },
-                            ],
-                            is_closed: false,
-                        },
+                                    bezier,
+                                })
+                                .collect(),
+                        }],
+                        mode: PaintMode::Stroke,
+                        winding_order: WindingOrder::NonZero,
                     },
-                ]);
+                });
             }
+        }
+        ops.push(Op::RestoreGraphicsState);
+        ops
+    }
+
+    /// Semi-transparent text across every copy, drawn as page content on top of the image and
+    /// clipped to the card.
+    pub(crate) fn watermark_ops(&mut self, slots: &[SlotRect]) -> Vec<Op> {
+        let style = self.cfg.watermark.clone();
+        let Some(text) = style.text.map(|text| text.replace("{date}", &today())) else {
+            return Vec::new();
+        };
+        // printpdf writes each alpha under the other's key, setting both covers the text fill
+        let gs = self.document.add_graphics_state(
+            ExtendedGraphicsState::default()
+                .with_current_fill_alpha(style.opacity)
+                .with_current_stroke_alpha(style.opacity),
+        );
+
+        let mut ops = Vec::new();
+        for slot in slots {
+            let card = slot.outset(self.cfg.bleed);
+            let angle = style
+                .angle
+                .unwrap_or_else(|| slot.height.0.atan2(slot.width.0).to_degrees());
+            // Without a size the text spans most of the card's diagonal
+            let size = style.font_size.unwrap_or_else(|| {
+                let span = Mm(slot.width.0.hypot(slot.height.0) * 0.85);
+                let width = self.text_width(&text, Pt(10.0));
+                Pt(10.0 * span.0 / width.0.max(0.01)).min(Pt::from(slot.height * 0.4))
+            });
+            let width = self.text_width(&text, size);
+            let center = Point::new(slot.x + slot.width / 2.0, slot.y + slot.height / 2.0);
 
-            current_x += self.cfg.card_width + self.cfg.horizontal_spacing;
+            ops.append(&mut vec![
+                Op::SaveGraphicsState,
+                rect_op(&card, PaintMode::Clip),
+                Op::LoadGraphicsState { gs: gs.clone() },
+                Op::SetFillColor {
+                    col: rgb_color(style.color),
+                },
+                Op::StartTextSection,
+                Op::SetTextMatrix {
+                    matrix: TextMatrix::TranslateRotate(center.x, center.y, angle),
+                },
+                // Centre the line on the rotated origin, roughly halfway up the capitals
+                Op::SetTextCursor {
+                    pos: Point::new(width / -2.0, Mm::from(size) * -0.35),
+                },
+            ]);
+            ops.append(&mut self.text_write_ops(&text, size));
+            ops.append(&mut vec![Op::EndTextSection, Op::RestoreGraphicsState]);
         }
-        ops.push(Op::DrawLine {
-            line: Line {
-                points: vec![
-                    LinePoint {
-                        p: Point {
-                            x: (self.cfg.page_width / 2.0).into_pt(),
-                            y: self.cfg.page_height.into_pt(),
-                        },
-                        bezier: false,
-                    },
-                    LinePoint {
+        ops
+    }
+
+    /// Thin outlines of the safe zone inside every slot, for checking only.
+    pub(crate) fn safe_zone_ops(&self, slots: &[SlotRect]) -> Vec<Op> {
+        if !self.cfg.show_safe_zone {
+            return Vec::new();
+        }
+
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetOutlineColor {
+                col: Color::Rgb(Rgb {
+                    r: 1.0,
+                    g: 0.0,
+                    b: 1.0,
+                    icc_profile: None,
+                }),
+            },
+            Op::SetOutlineThickness { pt: Pt(0.5) },
+            Op::SetLineDashPattern {
+                dash: LineDashPattern::default(),
+            },
+        ];
+        for slot in slots {
+            let zone = slot.inset(self.cfg.safe_zone);
+            ops.push(Op::DrawLine {
+                line: Line {
+                    points: [
+                        (zone.x, zone.y),
+                        (zone.right(), zone.y),
+                        (zone.right(), zone.top()),
+                        (zone.x, zone.top()),
+                    ]
+                    .into_iter()
+                    .map(|(x, y)| LinePoint {
                         p: Point {
-                            x: (self.cfg.page_width / 2.0).into_pt(),
-                            y: (current_y - self.cfg.vertical_spacing / 2.0).into_pt(),
+                            x: x.into_pt(),
+                            y: y.into_pt(),
                         },
                         bezier: false,
-                    },
-                ],
-                is_closed: false,
+                    })
+                    .collect(),
+                    is_closed: true,
+                },
+            });
+        }
+        ops.push(Op::RestoreGraphicsState);
+        ops
+    }
+
+    /// One caption line centred under or over the slot, inside the band the layout reserved.
+    fn caption_ops(&self, caption: &str, slot: &SlotRect) -> Vec<Op> {
+        let style = &self.cfg.caption;
+        let size = Mm::from(style.font_size);
+        let width = self.text_width(caption, style.font_size);
+        // Baselines leave room for ascenders below the card and for descenders above it
+        let y = match style.position {
+            CaptionPosition::Below => slot.y - self.cfg.bleed - style.gap - size * 0.9,
+            CaptionPosition::Above => slot.top() + self.cfg.bleed + style.gap + size * 0.25,
+        };
+
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetFillColor {
+                col: rgb_color([0.0, 0.0, 0.0]),
             },
-        });
+        ];
+        ops.append(&mut self.text_line_ops(
+            caption,
+            style.font_size,
+            Point::new(slot.x + (slot.width - width) / 2.0, y),
+        ));
+        ops.push(Op::RestoreGraphicsState);
+        ops
+    }
 
+    /// Everything in the footer band: the title, then the attestation block.
+    fn footer_ops(&self, text: Option<&str>, footer: FooterBand) -> Vec<Op> {
+        let mut ops = Vec::new();
         if let Some(text) = text {
-            ops.append(&mut vec![
-                // Save the graphics state to allow for position resets later
-                Op::SaveGraphicsState,
-                // Start a text section (required for text operations)
-                Op::StartTextSection,
+            ops.append(&mut self.title_ops(text, footer.top));
+        }
+        ops.append(&mut self.attestation_ops(
+            footer.top - self.title_depth(text) - ATTESTATION_GAP,
+            footer.floor,
+        ));
+        ops
+    }
+
+    /// The footer band from `bottom_y` down to the bottom margin.
+    fn footer_below(&self, bottom_y: Mm) -> FooterBand {
+        FooterBand {
+            top: bottom_y,
+            floor: self.cfg.margin_bottom,
+            above_cards: false,
+        }
+    }
+
+    /// The footer band below a page of grid slots and their captions.
+    fn grid_footer(&self, slots: &[SlotRect]) -> FooterBand {
+        self.footer_below(bottom_of(slots, self.first_row_y()) - self.cfg.caption_bands().0)
+    }
+
+    /// The footer band of a duplex back page. Turning the sheet over its top edge moves the
+    /// cards to the bottom of the back, so the footer goes above them, mirroring the band below
+    /// the front's cards: from the edge of the front's bottom margin down to the cards.
+    fn duplex_back_footer(&self, back_slots: &[SlotRect]) -> FooterBand {
+        if !duplex_flips_rows(&self.cfg) {
+            return self.grid_footer(back_slots);
+        }
+        let cards_top = back_slots
+            .iter()
+            .map(SlotRect::top)
+            .fold(self.cfg.margin_bottom, Mm::max);
+        FooterBand {
+            top: self.cfg.page_height - self.cfg.margin_bottom + self.cfg.duplex_offset_y,
+            floor: cards_top + self.cfg.caption_bands().1,
+            above_cards: true,
+        }
+    }
+
+    /// How far the title reaches below the grid, down to the descenders of its last line.
+    fn title_depth(&self, text: Option<&str>) -> Mm {
+        let Some(text) = text else {
+            return Mm(0.0);
+        };
+        let size = Mm::from(self.cfg.title.font_size);
+        let lines = self.title_lines(text).len();
+        TITLE_OFFSET + size * 1.2 * (lines.max(1) - 1) as f32 + size * 0.25
+    }
+
+    /// The config the grid is laid out with. With an attestation the bottom margin grows by the
+    /// title and the block under the grid, so fewer rows are laid out instead of the block being
+    /// left out. `texts` are the titles of the pages sharing the grid. A duplex back turned over
+    /// its top edge mirrors the grid, so the same room opens up above its cards.
+    fn grid_cfg(&self, texts: &[Option<&str>]) -> PageMarginConfig {
+        let mut cfg = self.cfg.clone();
+        if let Some((_, block_height)) = self.attestation_block() {
+            let title_depth = texts
+                .iter()
+                .map(|text| self.title_depth(*text))
+                .fold(Mm(0.0), Mm::max);
+            cfg.margin_bottom += title_depth + ATTESTATION_GAP + block_height;
+        }
+        cfg
+    }
+
+    fn title_lines(&self, text: &str) -> Vec<String> {
+        let style = &self.cfg.title;
+        let max_width = style
+            .max_width
+            .unwrap_or(self.cfg.page_width - self.cfg.margin_left * 2.0);
+        wrap_text(text, max_width, |line| {
+            self.text_width(line, style.font_size)
+        })
+    }
+
+    /// The title below the grid, wrapped and aligned between the page margins.
+    fn title_ops(&self, text: &str, bottom_y: Mm) -> Vec<Op> {
+        let style = &self.cfg.title;
+        let size = style.font_size;
+        let line_height = Mm::from(size) * 1.2;
+
+        let mut ops = vec![
+            // Save the graphics state to allow for position resets later
+            Op::SaveGraphicsState,
+            Op::SetFillColor {
+                col: rgb_color([0.0, 0.0, 0.0]),
+            },
+        ];
+        for (idx, line) in self.title_lines(text).into_iter().enumerate() {
+            let width = self.text_width(&line, size);
+            let x = match style.align {
+                TextAlign::Left => self.cfg.margin_left,
+                TextAlign::Center => (self.cfg.page_width - width) / 2.0,
+                TextAlign::Right => self.cfg.page_width - self.cfg.margin_left - width,
+            };
+            ops.append(&mut self.text_line_ops(
+                &line,
+                size,
                 // Position the text cursor from the bottom left
-                Op::SetTextCursor {
-                    pos: Point::new(
-                        (self.cfg.page_width / 2.0) - Mm(text.len() as f32) * 1.8,
-                        current_y - Mm(20.0),
-                    ),
-                },
-                // Set a built-in font (Helvetica) with its size
-                Op::SetFontSizeBuiltinFont {
-                    size: Pt(20.0),
-                    font: BuiltinFont::Helvetica,
-                },
-                Op::SetLineHeight { lh: Pt(20.0) },
-                // Set text color to blue
-                Op::SetFillColor {
-                    col: Color::Rgb(Rgb {
-                        r: 0.0,
-                        g: 0.0,
-                        b: 0.0,
-                        icc_profile: None,
-                    }),
-                },
-                // Write text with the built-in font
-                Op::WriteTextBuiltinFont {
-                    items: vec![TextItem::Text(text)],
-                    font: BuiltinFont::Helvetica,
-                },
-                // End the text section
-                Op::EndTextSection,
-                // Restore the graphics state
-                Op::RestoreGraphicsState,
-            ]);
+                Point::new(x, bottom_y - TITLE_OFFSET - line_height * idx as f32),
+            ));
         }
+        // Restore the graphics state
+        ops.push(Op::RestoreGraphicsState);
+        ops
+    }
 
-        self.add_page_to_document(ops);
+    /// The wrapped declaration and the height of the whole attestation block, `None` without
+    /// a declaration.
+    fn attestation_block(&self) -> Option<(Vec<String>, Mm)> {
+        let style = &self.cfg.attestation;
+        let declaration = style.declaration.as_ref()?.replace("{date}", &today());
+        let size = style.font_size;
+        let line_height = Mm::from(size) * 1.2;
+        let lines = wrap_text(
+            &declaration,
+            self.cfg.page_width - self.cfg.margin_left * 2.0,
+            |line| self.text_width(line, size),
+        );
+        // The signature and date lines with their labels, or the stamp box if that is taller
+        let fields =
+            (SIGNATURE_SPACE + line_height + DATE_SPACE + line_height).max(style.stamp_size);
+        let height = line_height * lines.len() as f32 + ATTESTATION_GAP + fields;
+        Some((lines, height))
+    }
+
+    /// Reports an attestation block that does not fit below the grid and is left out.
+    fn check_attestation(&self, text: Option<&str>, footer: FooterBand) -> Option<LayoutIssue> {
+        let (_, block_height) = self.attestation_block()?;
+        let block_bottom = footer.top - self.title_depth(text) - ATTESTATION_GAP - block_height;
+        (block_bottom < footer.floor).then(|| {
+            LayoutIssue::new(
+                IssueKind::Omitted,
+                format!(
+                    "The attestation block needs {:.1} mm more {} the cards and is left out",
+                    (footer.floor - block_bottom).0,
+                    if footer.above_cards { "above" } else { "below" }
+                ),
+                &[
+                    "attestation.stamp_size",
+                    "attestation.font_size",
+                    "margin_bottom",
+                    "center",
+                ],
+            )
+        })
+    }
+
+    /// Declaration, signature and date lines on the left and a stamp box on the right, hanging
+    /// from `top_y`. Left out with a warning when it does not fit above `floor`.
+    fn attestation_ops(&self, top_y: Mm, floor: Mm) -> Vec<Op> {
+        let Some((lines, block_height)) = self.attestation_block() else {
+            return Vec::new();
+        };
+        if top_y - block_height < floor {
+            println!("No room for the attestation next to the cards, leaving it out");
+            return Vec::new();
+        }
+        let style = &self.cfg.attestation;
+        let size = style.font_size;
+        let line_height = Mm::from(size) * 1.2;
+        let left = self.cfg.margin_left;
+        let right = self.cfg.page_width - self.cfg.margin_left;
+        let stamp_left = right - style.stamp_size;
+
+        let fields_top = top_y - line_height * lines.len() as f32 - ATTESTATION_GAP;
+        let signature_y = fields_top - SIGNATURE_SPACE;
+        let date_y = signature_y - line_height - DATE_SPACE;
+
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetFillColor {
+                col: rgb_color([0.0, 0.0, 0.0]),
+            },
+            Op::SetOutlineColor {
+                col: rgb_color([0.0, 0.0, 0.0]),
+            },
+            Op::SetOutlineThickness { pt: Pt(0.5) },
+            Op::SetLineDashPattern {
+                dash: LineDashPattern::default(),
+            },
+        ];
+        for (idx, line) in lines.iter().enumerate() {
+            // Baselines sit a descender's height above the bottom of each line
+            let y = top_y - line_height * (idx + 1) as f32 + Mm::from(size) * 0.25;
+            ops.append(&mut self.text_line_ops(line, size, Point::new(left, y)));
+        }
+
+        let line_end = (left + SIGNATURE_LINE_LENGTH).min(stamp_left - ATTESTATION_GAP);
+        for (label, y) in [("Signature", signature_y), ("Date", date_y)] {
+            ops.push(line_op((left, y), (line_end, y)));
+            ops.append(&mut self.text_line_ops(
+                label,
+                size,
+                Point::new(left, y - line_height + Mm::from(size) * 0.25),
+            ));
+        }
+
+        if let Some((image, image_id)) = &self.signature {
+            // Fit the scan into the space above the line, keeping its proportions
+            let (max_width, max_height) = (line_end - left, SIGNATURE_SPACE);
+            let aspect = image.width as f32 / image.height.max(1) as f32;
+            let (width, height) = if max_width.0 / max_height.0 > aspect {
+                (max_height * aspect, max_height)
+            } else {
+                (max_width, max_width / aspect)
+            };
+            let area = SlotRect {
+                x: left,
+                y: signature_y,
+                width,
+                height,
+                rotation: 0,
+            };
+            ops.append(&mut image_ops(
+                image,
+                image_id,
+                (&area, &area),
+                FitMode::Stretch,
+                self.cfg.source_dpi,
+            ));
+        }
+
+        ops.push(rect_op(
+            &SlotRect {
+                x: stamp_left,
+                y: fields_top - style.stamp_size,
+                width: style.stamp_size,
+                height: style.stamp_size,
+                rotation: 0,
+            },
+            PaintMode::Stroke,
+        ));
+        ops.append(&mut self.text_line_ops(
+            "Stamp",
+            size * 0.8,
+            Point::new(
+                stamp_left + Mm(1.5),
+                fields_top - Mm(1.5) - Mm::from(size * 0.8),
+            ),
+        ));
+        ops.push(Op::RestoreGraphicsState);
+        ops
     }
 }
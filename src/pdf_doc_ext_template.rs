@@ -0,0 +1,256 @@
+use std::collections::{HashMap, hash_map::Entry};
+
+use crate::{
+    configs::{CutGuideKind, TextAlign},
+    layout::SlotRect,
+    pdf_doc_ext_idcard::{GuideShape, caption_text, fold_style_ops, line_dash_op, rgb_color},
+    pdf_doc_util::PdfDocUtil,
+    template::{LayoutTemplate, TemplateCut, TemplateSlot, TemplateText},
+    text_metrics::wrap_text,
+    validation::{LayoutIssue, check_slots},
+};
+
+use printpdf::{
+    CurTransMat, Line, LineDashPattern, LinePoint, Mm, Op, Point, Pt, RawImage, XObjectId,
+};
+
+pub trait PdfDocTemplateExt {
+    /// Renders every page of `template`, filling each slot with the input image it names. The
+    /// page size switches to the template's and the bleed is dropped for the rest of the
+    /// document.
+    fn add_template(
+        &mut self,
+        template: &LayoutTemplate,
+        image_paths: &[String],
+        text: Option<String>,
+    ) -> Result<(), String>;
+
+    /// Reports template slots that overlap, leave the template's page or reach into the
+    /// unprintable strips, page by page.
+    fn check_template(&self, template: &LayoutTemplate) -> Vec<LayoutIssue>;
+}
+
+impl PdfDocTemplateExt for PdfDocUtil {
+    fn add_template(
+        &mut self,
+        template: &LayoutTemplate,
+        image_paths: &[String],
+        text: Option<String>,
+    ) -> Result<(), String> {
+        let needed = template.input_count();
+        if image_paths.len() < needed {
+            return Err(format!(
+                "Template {} uses {} input images, {} given.",
+                template.name.as_deref().unwrap_or("without a name"),
+                needed,
+                image_paths.len()
+            ));
+        }
+        (self.cfg.page_width, self.cfg.page_height) = template.page_size();
+        // A slot is the whole footprint, as `check_template` assumes
+        self.cfg.bleed = Mm(0.0);
+
+        // Each input is loaded once, however many slots show it
+        let mut images: HashMap<usize, (RawImage, XObjectId)> = HashMap::new();
+        for page in &template.pages {
+            for slot in &page.slots {
+                if let Entry::Vacant(entry) = images.entry(slot.input) {
+                    let image = self.load_and_process_image(&image_paths[slot.input]);
+                    let image_id = self.document.add_image(&image);
+                    entry.insert((image, image_id));
+                }
+            }
+        }
+
+        let total = template.pages.len();
+        for (index, page) in template.pages.iter().enumerate() {
+            let footprints: Vec<SlotRect> = page.slots.iter().map(TemplateSlot::rect).collect();
+
+            let mut ops = Vec::new();
+            for slot in &page.slots {
+                let (image, image_id) = &images[&slot.input];
+                ops.append(&mut self.template_image_ops(image, image_id, slot));
+            }
+            ops.append(&mut self.watermark_ops(&footprints));
+            ops.append(&mut self.border_ops(&footprints));
+            if page.cuts.iter().all(|cut| cut.fold) {
+                // Template slots are not spaced by the grid settings, the cut follows the slot
+                ops.append(&mut self.cut_guide_ops(&footprints, GuideShape::TrimLines));
+            }
+            ops.append(&mut self.cut_path_ops(&page.cuts));
+            ops.append(&mut self.safe_zone_ops(&footprints));
+            ops.append(&mut self.registration_mark_ops());
+            for text_box in &page.texts {
+                let content = caption_text(&text_box.text, index + 1, total, text.as_deref());
+                ops.append(&mut self.text_box_ops(text_box, &content));
+            }
+
+            self.add_page_to_document(ops);
+        }
+        Ok(())
+    }
+
+    fn check_template(&self, template: &LayoutTemplate) -> Vec<LayoutIssue> {
+        let mut cfg = self.cfg.clone();
+        (cfg.page_width, cfg.page_height) = template.page_size();
+        // A slot is the whole footprint, templates add no bleed or captions around it
+        cfg.bleed = Mm(0.0);
+        cfg.caption.template = None;
+        let mut issues = Vec::new();
+        for (index, page) in template.pages.iter().enumerate() {
+            let slots: Vec<SlotRect> = page.slots.iter().map(TemplateSlot::rect).collect();
+            issues.append(&mut check_slots(
+                &cfg,
+                &slots,
+                &format!("template's page {}", index + 1),
+            ));
+        }
+        // The slots come from the template, not the grid settings
+        for issue in &mut issues {
+            issue
+                .fields
+                .retain(|field| matches!(*field, "unprintable" | "calibration"));
+            issue.fields.insert(0, "template");
+        }
+        issues
+    }
+}
+
+impl PdfDocUtil {
+    /// The image of a template slot, turned about the centre of its footprint.
+    fn template_image_ops(
+        &self,
+        image: &RawImage,
+        image_id: &XObjectId,
+        slot: &TemplateSlot,
+    ) -> Vec<Op> {
+        let footprint = slot.rect();
+        if slot.rotation == 0 {
+            return self.card_image_ops(image, image_id, &footprint);
+        }
+
+        // Lay the card out upright around the same centre, then turn the whole drawing
+        let (center_x, center_y) = (
+            footprint.x + footprint.width / 2.0,
+            footprint.y + footprint.height / 2.0,
+        );
+        let (width, height) = if slot.rotation % 180 == 90 {
+            (footprint.height, footprint.width)
+        } else {
+            (footprint.width, footprint.height)
+        };
+        let card = SlotRect {
+            x: center_x - width / 2.0,
+            y: center_y - height / 2.0,
+            width,
+            height,
+            rotation: 0,
+        };
+
+        let angle = (slot.rotation as f32).to_radians();
+        let (sin, cos) = (angle.sin().round(), angle.cos().round());
+        let (cx, cy) = (center_x.into_pt().0, center_y.into_pt().0);
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetTransformationMatrix {
+                matrix: CurTransMat::Raw([
+                    cos,
+                    sin,
+                    -sin,
+                    cos,
+                    cx - cos * cx + sin * cy,
+                    cy - sin * cx - cos * cy,
+                ]),
+            },
+        ];
+        ops.append(&mut self.card_image_ops(image, image_id, &card));
+        ops.push(Op::RestoreGraphicsState);
+        ops
+    }
+
+    /// The template's own cut paths in the colour, width and dash of the cut guides, and its
+    /// folds in the fold style.
+    fn cut_path_ops(&self, cuts: &[TemplateCut]) -> Vec<Op> {
+        let (folds, cuts): (Vec<&TemplateCut>, Vec<&TemplateCut>) =
+            cuts.iter().partition(|cut| cut.fold);
+        let mut ops = Vec::new();
+
+        let style = &self.cfg.cut_guides;
+        if style.kind != CutGuideKind::None && !cuts.is_empty() {
+            ops.extend([
+                Op::SaveGraphicsState,
+                Op::SetOutlineColor {
+                    col: rgb_color(style.color),
+                },
+                Op::SetOutlineThickness {
+                    pt: style.width.unwrap_or(Pt(1.0)),
+                },
+                match style.dash {
+                    Some((dash, gap)) => line_dash_op(dash, gap),
+                    None => Op::SetLineDashPattern {
+                        dash: LineDashPattern::default(),
+                    },
+                },
+            ]);
+            ops.extend(cuts.into_iter().map(path_op));
+            ops.push(Op::RestoreGraphicsState);
+        }
+
+        if !folds.is_empty() {
+            ops.push(Op::SaveGraphicsState);
+            ops.append(&mut fold_style_ops());
+            ops.extend(folds.into_iter().map(path_op));
+            ops.push(Op::RestoreGraphicsState);
+        }
+        ops
+    }
+
+    /// A template text block, wrapped to its width when it has one.
+    fn text_box_ops(&self, text_box: &TemplateText, content: &str) -> Vec<Op> {
+        let size = Pt(text_box.font_size);
+        let line_height = Mm::from(size) * 1.2;
+        let lines: Vec<String> = match text_box.width {
+            Some(width) => wrap_text(content, Mm(width), |line| self.text_width(line, size)),
+            None => content.lines().map(str::to_string).collect(),
+        };
+        // Without a width the alignment is around `x` itself
+        let span = Mm(text_box.width.unwrap_or(0.0));
+
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetFillColor {
+                col: rgb_color([0.0, 0.0, 0.0]),
+            },
+        ];
+        for (idx, line) in lines.iter().enumerate() {
+            let width = self.text_width(line, size);
+            let x = Mm(text_box.x)
+                + match text_box.align {
+                    TextAlign::Left => Mm(0.0),
+                    TextAlign::Center => (span - width) / 2.0,
+                    TextAlign::Right => span - width,
+                };
+            // The first baseline sits one font size below the top of the block
+            let y = Mm(text_box.y) - Mm::from(size) - line_height * idx as f32;
+            ops.append(&mut self.text_line_ops(line, size, Point::new(x, y)));
+        }
+        ops.push(Op::RestoreGraphicsState);
+        ops
+    }
+}
+
+fn path_op(cut: &TemplateCut) -> Op {
+    Op::DrawLine {
+        line: Line {
+            points: cut
+                .points
+                .iter()
+                .map(|&[x, y]| LinePoint {
+                    p: Point::new(Mm(x), Mm(y)),
+                    bezier: false,
+                })
+                .collect(),
+            is_closed: cut.closed,
+        },
+    }
+}
@@ -1,19 +1,24 @@
 use image::DynamicImage;
-use printpdf::{Mm, Op, PdfDocument, PdfPage, PdfSaveOptions, RawImage};
+use printpdf::{
+    BuiltinFont, CurTransMat, Mm, Op, ParsedFont, PdfDocument, PdfPage, PdfSaveOptions, Point, Pt,
+    RawImage, TextItem, XObjectId,
+};
 
-use crate::{configs::PageMarginConfig, extensions::RawImageExt};
-
-pub(crate) fn calc_avg_dpi(width: &Mm, height: &Mm, img: &RawImage) -> f32 {
-    let width_in = width.0 / 25.4_f32;
-    let height_in = height.0 / 25.4_f32;
-    let width_dpi = img.width as f32 / width_in;
-    let height_dpi = img.height as f32 / height_in;
-    (width_dpi + height_dpi) / 2.0
-}
+use crate::{
+    configs::PageMarginConfig,
+    extensions::RawImageExt,
+    layout::{GridLayout, LayoutStrategy},
+    text_metrics::{EmbeddedFont, helvetica_text_width},
+};
 
 pub struct PdfDocUtil {
     pub(crate) document: PdfDocument,
     pub(crate) cfg: PageMarginConfig,
+    pub(crate) layout: Box<dyn LayoutStrategy>,
+    /// Font for all text, the built-in Helvetica when `None`
+    pub(crate) font: Option<EmbeddedFont>,
+    /// Signature scan drawn on the attestation's signature line
+    pub(crate) signature: Option<(RawImage, XObjectId)>,
     image_processors: Vec<Box<dyn FnMut(DynamicImage) -> DynamicImage>>, // List of processing callbacks
 }
 impl PdfDocUtil {
@@ -21,10 +26,42 @@ impl PdfDocUtil {
         Self {
             document: PdfDocument::new("Image Example"),
             cfg,
+            layout: Box::new(GridLayout),
+            font: None,
+            signature: None,
             image_processors: Vec::new(),
         }
     }
 
+    /// Replaces the default grid with a custom slot arrangement.
+    pub fn set_layout_strategy<L>(&mut self, layout: L)
+    where
+        L: LayoutStrategy + 'static,
+    {
+        self.layout = Box::new(layout);
+    }
+
+    /// Embeds a TrueType or OpenType font and uses it for all text from now on. Only the glyphs
+    /// used end up in the PDF.
+    pub fn set_font(&mut self, font_path: &str) -> Result<(), String> {
+        let bytes = std::fs::read(font_path).map_err(|e| format!("{}: {}", font_path, e))?;
+        let parsed = ParsedFont::from_bytes(&bytes, 0, &mut Vec::new())
+            .ok_or_else(|| format!("{}: unsupported font file", font_path))?;
+        let id = self.document.add_font(&parsed);
+        self.font = Some(EmbeddedFont::new(id, bytes)?);
+        Ok(())
+    }
+
+    /// Loads a signature scan for the attestation block. It is not run through the image
+    /// processors.
+    pub fn set_signature_image(&mut self, image_path: &str) -> Result<(), String> {
+        let image = image::open(image_path).map_err(|e| format!("{}: {}", image_path, e))?;
+        let image = RawImage::from_dynamic_image(image, &mut Vec::new())?;
+        let id = self.document.add_image(&image);
+        self.signature = Some((image, id));
+        Ok(())
+    }
+
     pub fn register_image_processor<F>(&mut self, callback: F)
     where
         F: FnMut(DynamicImage) -> DynamicImage + 'static, // Use FnMut instead of FnOnce
@@ -32,12 +69,73 @@ impl PdfDocUtil {
         self.image_processors.push(Box::new(callback)); // Store the callback as a boxed trait object
     }
 
-    pub(crate) fn add_page_to_document(&mut self, ops: Vec<Op>) {
+    /// Adds a page with `ops`, wrapped in the printer calibration when there is one.
+    pub(crate) fn add_page_to_document(&mut self, mut ops: Vec<Op>) {
+        let calibration = self.cfg.calibration;
+        if !calibration.is_identity() {
+            let (origin_x, origin_y) = self.cfg.device_point(Mm(0.0), Mm(0.0));
+            ops.insert(0, Op::SaveGraphicsState);
+            ops.insert(
+                1,
+                Op::SetTransformationMatrix {
+                    matrix: CurTransMat::Raw([
+                        calibration.scale_x,
+                        0.0,
+                        0.0,
+                        calibration.scale_y,
+                        origin_x.into_pt().0,
+                        origin_y.into_pt().0,
+                    ]),
+                },
+            );
+            ops.push(Op::RestoreGraphicsState);
+        }
         let page = PdfPage::new(self.cfg.page_width, self.cfg.page_height, ops);
         self.document.with_pages(vec![page]);
     }
 
-    pub(crate) fn load_and_process_image(&mut self, image_path: &String) -> RawImage {
+    /// Width of one line of text in the document font.
+    pub(crate) fn text_width(&self, text: &str, size: Pt) -> Mm {
+        match &self.font {
+            Some(font) => font.text_width(text, size),
+            None => helvetica_text_width(text, size),
+        }
+    }
+
+    /// Writes one line of text in the document font with its baseline starting at `pos`. The
+    /// fill colour is left to the caller.
+    pub(crate) fn text_line_ops(&self, text: &str, size: Pt, pos: Point) -> Vec<Op> {
+        let mut ops = vec![Op::StartTextSection, Op::SetTextCursor { pos }];
+        ops.append(&mut self.text_write_ops(text, size));
+        ops.push(Op::EndTextSection);
+        ops
+    }
+
+    /// Selects the document font and writes `text` at the current text position.
+    pub(crate) fn text_write_ops(&self, text: &str, size: Pt) -> Vec<Op> {
+        match &self.font {
+            Some(font) => {
+                let mut ops = vec![Op::SetFontSize {
+                    size,
+                    font: font.id.clone(),
+                }];
+                ops.append(&mut font.shape(text, size).1);
+                ops
+            }
+            None => vec![
+                Op::SetFontSizeBuiltinFont {
+                    size,
+                    font: BuiltinFont::Helvetica,
+                },
+                Op::WriteTextBuiltinFont {
+                    items: vec![TextItem::Text(text.to_string())],
+                    font: BuiltinFont::Helvetica,
+                },
+            ],
+        }
+    }
+
+    pub(crate) fn load_and_process_image(&mut self, image_path: &str) -> RawImage {
         print!("Loading Image {} ... ", image_path);
         let mut image = image::open(image_path).expect("Failed to open image");
         println!("Loaded");
@@ -52,16 +150,26 @@ impl PdfDocUtil {
         };
         println!("Image Processed");
 
-        let image = RawImage::from_dynamic_image(image, &mut Vec::new()).unwrap();
-        image
+        RawImage::from_dynamic_image(image, &mut Vec::new()).unwrap()
     }
 
     pub fn serialize_pdf(&self) -> Vec<u8> {
-        self.document
-            .save(&PdfSaveOptions::default(), &mut Vec::new())
+        let Some(font) = &self.font else {
+            return self
+                .document
+                .save(&PdfSaveOptions::default(), &mut Vec::new());
+        };
+        // The glyph runs become raw `TJ` operations, which printpdf only writes when not secure
+        let mut document = self.document.clone();
+        let raw_runs = font.encode_glyph_runs(&mut document.pages);
+        let options = PdfSaveOptions {
+            secure: !raw_runs,
+            ..PdfSaveOptions::default()
+        };
+        document.save(&options, &mut Vec::new())
     }
 
-    pub fn save_pdf(&self, pdf_path: &String) {
+    pub fn save_pdf(&self, pdf_path: &str) {
         let bytes = self.serialize_pdf();
         std::fs::write(pdf_path, bytes).unwrap();
         println!("Created {}", pdf_path);
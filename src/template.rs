@@ -0,0 +1,181 @@
+use std::path::Path;
+
+use printpdf::Mm;
+use serde::Deserialize;
+
+use crate::{configs::TextAlign, layout::SlotRect};
+
+/// A fixed page arrangement read from a TOML or JSON file, for forms and sheets the grid layout
+/// cannot produce. All lengths are in millimetres from the bottom left corner of the page.
+///
+/// ```toml
+/// name = "ID-1 front and back"
+/// page_width = 210.0
+/// page_height = 297.0
+///
+/// [[pages]]
+/// slots = [
+///     { x = 20.0, y = 220.0, width = 85.6, height = 53.98, input = 0 },
+///     { x = 105.6, y = 220.0, width = 85.6, height = 53.98, input = 1 },
+/// ]
+/// cuts = [{ points = [[20.0, 220.0], [191.2, 220.0], [191.2, 273.98], [20.0, 273.98]], closed = true }]
+/// texts = [{ x = 20.0, y = 200.0, text = "Printed on {date}" }]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutTemplate {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub page_width: f32,
+    pub page_height: f32,
+    pub pages: Vec<TemplatePage>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplatePage {
+    #[serde(default)]
+    pub slots: Vec<TemplateSlot>,
+    /// Cut paths drawn in the cut guide style, every slot gets an outline when there are none
+    /// but folds
+    #[serde(default)]
+    pub cuts: Vec<TemplateCut>,
+    #[serde(default)]
+    pub texts: Vec<TemplateText>,
+}
+
+/// Where one input image goes. The rectangle is the image's footprint on the page.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateSlot {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Counter-clockwise quarter turn in degrees: 0, 90, 180 or 270
+    #[serde(default)]
+    pub rotation: u16,
+    /// Index into the input images, so 0 and 1 are the front and back of a two sided card
+    #[serde(default)]
+    pub input: usize,
+}
+
+impl TemplateSlot {
+    pub fn rect(&self) -> SlotRect {
+        SlotRect {
+            x: Mm(self.x),
+            y: Mm(self.y),
+            width: Mm(self.width),
+            height: Mm(self.height),
+            rotation: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateCut {
+    pub points: Vec<[f32; 2]>,
+    /// Joins the last point back to the first
+    #[serde(default)]
+    pub closed: bool,
+    /// A line to fold along rather than cut, drawn in black dashes like the fold of a card pair
+    #[serde(default)]
+    pub fold: bool,
+}
+
+/// A block of text with its first line's top at `y`. The placeholders `{title}`, `{date}`,
+/// `{index}` (page number) and `{total}` (page count) are filled in.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateText {
+    pub x: f32,
+    pub y: f32,
+    /// Lines are wrapped to this width and aligned within it, `None` writes them as they are
+    #[serde(default)]
+    pub width: Option<f32>,
+    pub text: String,
+    #[serde(default = "default_text_size")]
+    pub font_size: f32,
+    #[serde(default = "default_text_align")]
+    pub align: TextAlign,
+}
+
+fn default_text_size() -> f32 {
+    10.0
+}
+
+fn default_text_align() -> TextAlign {
+    TextAlign::Left
+}
+
+impl LayoutTemplate {
+    /// Reads a template, as JSON for a `.json` file and as TOML otherwise.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let template = if is_json {
+            Self::from_json(&source)
+        } else {
+            Self::from_toml(&source)
+        };
+        template.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self, String> {
+        let template: Self = toml::from_str(source).map_err(|e| e.to_string())?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    pub fn from_json(source: &str) -> Result<Self, String> {
+        let template: Self = serde_json::from_str(source).map_err(|e| e.to_string())?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    pub fn page_size(&self) -> (Mm, Mm) {
+        (Mm(self.page_width), Mm(self.page_height))
+    }
+
+    /// Number of input images the slots refer to.
+    pub fn input_count(&self) -> usize {
+        self.pages
+            .iter()
+            .flat_map(|page| &page.slots)
+            .map(|slot| slot.input + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.page_width <= 0.0 || self.page_height <= 0.0 {
+            return Err("page size must be positive".into());
+        }
+        for (index, page) in self.pages.iter().enumerate() {
+            for slot in &page.slots {
+                if slot.width <= 0.0 || slot.height <= 0.0 {
+                    return Err(format!("page {}: slot size must be positive", index + 1));
+                }
+                if slot.rotation % 90 != 0 || slot.rotation >= 360 {
+                    return Err(format!(
+                        "page {}: slot rotation must be 0, 90, 180 or 270, not {}",
+                        index + 1,
+                        slot.rotation
+                    ));
+                }
+            }
+            if let Some(cut) = page.cuts.iter().find(|cut| cut.points.len() < 2) {
+                return Err(format!(
+                    "page {}: cut path needs at least two points, got {}",
+                    index + 1,
+                    cut.points.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::BTreeMap;
+
+use printpdf::{DictItem, FontId, Mm, Op, PdfPage, Pt, TextRenderingMode};
+use rustybuzz::{Direction, Face, UnicodeBuffer, ttf_parser::GlyphId};
+use unicode_bidi::BidiInfo;
+
+/// Advance widths of Helvetica in 1/1000 em for the printable ASCII range, from its AFM file.
+const HELVETICA_ASCII: [u16; 95] = [
+    // ' '..'/'
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    // '0'..'?'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    // '@'..'O'
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    // 'P'..'_'
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    // '`'..'o'
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    // 'p'..'~'
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Advance widths of Helvetica for U+00A0..U+00FF.
+const HELVETICA_LATIN1: [u16; 96] = [
+    // nbsp..macron
+    278, 333, 556, 556, 556, 556, 260, 556, 333, 737, 370, 556, 584, 333, 737, 333,
+    // degree..'¿'
+    400, 584, 333, 333, 333, 556, 537, 278, 333, 333, 365, 556, 834, 834, 834, 611,
+    // 'À'..'Ï'
+    667, 667, 667, 667, 667, 667, 1000, 722, 667, 667, 667, 667, 278, 278, 278, 278,
+    // 'Ð'..'ß'
+    722, 722, 778, 778, 778, 778, 778, 584, 778, 722, 722, 722, 722, 667, 667, 611,
+    // 'à'..'ï'
+    556, 556, 556, 556, 556, 556, 889, 500, 556, 556, 556, 556, 278, 278, 278, 278,
+    // 'ð'..'ÿ'
+    556, 556, 556, 556, 556, 556, 556, 584, 611, 556, 556, 556, 556, 500, 556, 500,
+];
+
+// Characters without an entry are measured as a digit, the typical width of the font
+const FALLBACK_WIDTH: u16 = 556;
+
+/// Advance width of one character of the built-in Helvetica in 1/1000 em.
+pub fn helvetica_char_width(c: char) -> u16 {
+    match c as u32 {
+        code @ 0x20..=0x7e => HELVETICA_ASCII[(code - 0x20) as usize],
+        code @ 0xa0..=0xff => HELVETICA_LATIN1[(code - 0xa0) as usize],
+        _ => FALLBACK_WIDTH,
+    }
+}
+
+/// Width of `text` set in the built-in Helvetica at `size`.
+pub fn helvetica_text_width(text: &str, size: Pt) -> Mm {
+    let units: u32 = text.chars().map(|c| helvetica_char_width(c) as u32).sum();
+    Mm::from(Pt(units as f32 * size.0 / 1000.0))
+}
+
+/// Breaks `text` into lines no wider than `max_width` as measured by `measure`.
+///
+/// Lines are broken between words, explicit newlines are kept, and a single word that does not
+/// fit on its own is split between characters.
+pub fn wrap_text(text: &str, max_width: Mm, measure: impl Fn(&str) -> Mm) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if measure(&candidate) <= max_width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if line.chars().count() > 1 && measure(&line) > max_width {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// A TrueType or OpenType font embedded in the document, shaped with its own layout tables so
+/// joining scripts get their contextual forms and right-to-left runs come out in visual order.
+pub struct EmbeddedFont {
+    pub id: FontId,
+    bytes: Vec<u8>,
+}
+
+impl EmbeddedFont {
+    /// Checks that `bytes` hold a usable font; `id` is where the document registered it.
+    pub fn new(id: FontId, bytes: Vec<u8>) -> Result<Self, String> {
+        Face::from_slice(&bytes, 0).ok_or("Unsupported font file.")?;
+        Ok(Self { id, bytes })
+    }
+
+    /// Width of `text` set in this font at `size`.
+    pub fn text_width(&self, text: &str, size: Pt) -> Mm {
+        self.shape(text, size).0
+    }
+
+    /// Shapes one line in visual order and returns its width with the ops that write it at the
+    /// current text position.
+    ///
+    /// Glyphs are written by their IDs, so contextual forms and ligatures the font has no
+    /// character for come out as shaped. Kerning moves each glyph from where the previous
+    /// glyph's advance leaves the pen to where the shaper placed it, and the text rise lifts or
+    /// lowers marks such as Arabic diacritics.
+    pub fn shape(&self, text: &str, size: Pt) -> (Mm, Vec<Op>) {
+        let Some(face) = Face::from_slice(&self.bytes, 0) else {
+            return (Mm(0.0), Vec::new());
+        };
+        let units_per_em = face.units_per_em() as f32;
+        // Kerning and PDF glyph widths are in 1/1000 em
+        let to_thousandths = |units: i32| (units as f32 * 1000.0 / units_per_em).round() as i64;
+
+        let mut ops = Vec::new();
+        let mut glyphs = Vec::new();
+        let mut rise = 0;
+        // Pen position in font units as shaped, and in 1/1000 em as the PDF viewer advances it
+        let (mut pen, mut pdf_pen) = (0_i32, 0_i64);
+        for (run, rtl) in visual_runs(text) {
+            let mut buffer = UnicodeBuffer::new();
+            buffer.push_str(run);
+            buffer.guess_segment_properties();
+            buffer.set_direction(if rtl {
+                Direction::RightToLeft
+            } else {
+                Direction::LeftToRight
+            });
+            let shaped = rustybuzz::shape(&face, &[], buffer);
+
+            for (info, position) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+                if position.y_offset != rise {
+                    if !glyphs.is_empty() {
+                        ops.push(Op::WriteCodepointsWithKerning {
+                            font: self.id.clone(),
+                            cpk: std::mem::take(&mut glyphs),
+                        });
+                    }
+                    rise = position.y_offset;
+                    ops.push(Op::SetLineOffset {
+                        multiplier: rise as f32 * size.0 / units_per_em,
+                    });
+                }
+
+                let gid = info.glyph_id as u16;
+                // The first character of the cluster, so the text can still be copied
+                let source = run[info.cluster as usize..].chars().next().unwrap_or(' ');
+                let target = to_thousandths(pen + position.x_offset);
+                glyphs.push((pdf_pen - target, gid, source));
+
+                // The PDF widths are the font's advances, truncated to 1/1000 em
+                let advance = face.glyph_hor_advance(GlyphId(gid)).unwrap_or(0) as f32;
+                pdf_pen = target + (advance * 1000.0 / units_per_em) as i64;
+                pen += position.x_advance;
+            }
+        }
+        if !glyphs.is_empty() {
+            ops.push(Op::WriteCodepointsWithKerning {
+                font: self.id.clone(),
+                cpk: glyphs,
+            });
+        }
+        if rise != 0 {
+            ops.push(Op::SetLineOffset { multiplier: 0.0 });
+        }
+        let width = Mm::from(Pt(pen as f32 * size.0 / units_per_em));
+        (width, ops)
+    }
+
+    /// Turns the glyph runs of this font on `pages` into `TJ` operations that address the glyphs
+    /// by their number in the subset font the PDF embeds.
+    ///
+    /// printpdf 0.8 encodes `WriteCodepoints` by looking each new glyph number up in the
+    /// subset's cmap as if it were a character, which makes every glyph glyph 0. The glyphs are
+    /// registered in one invisible run instead, so they still go into the subset together with
+    /// their widths and the characters they copy as, and the runs are written with the numbers
+    /// printpdf hands out: the rank of the original glyph ID among all used ones and the space
+    /// glyph. That numbering is printpdf's internal business, which is why the dependency is
+    /// pinned to the exact version this was written against.
+    ///
+    /// Returns whether there were any runs, which then need saving with `secure` off.
+    pub(crate) fn encode_glyph_runs(&self, pages: &mut [PdfPage]) -> bool {
+        let mut used: BTreeMap<u16, char> = BTreeMap::new();
+        for op in pages.iter().flat_map(|page| &page.ops) {
+            if let Op::WriteCodepointsWithKerning { font, cpk } = op
+                && *font == self.id
+            {
+                used.extend(cpk.iter().map(|&(_, gid, ch)| (gid, ch)));
+            }
+        }
+        let Some(first_page) = pages.first_mut() else {
+            return false;
+        };
+        if used.is_empty() {
+            return false;
+        }
+
+        // printpdf always keeps the space glyph
+        let mut subset_ids: Vec<u16> = used.keys().copied().collect();
+        if let Some(space) = Face::from_slice(&self.bytes, 0).and_then(|face| face.glyph_index(' '))
+        {
+            subset_ids.push(space.0);
+        }
+        subset_ids.sort_unstable();
+        subset_ids.dedup();
+
+        first_page.ops.extend([
+            Op::SaveGraphicsState,
+            Op::StartTextSection,
+            Op::SetFontSize {
+                size: Pt(1.0),
+                font: self.id.clone(),
+            },
+            Op::SetTextRenderingMode {
+                mode: TextRenderingMode::Invisible,
+            },
+            Op::WriteCodepoints {
+                font: self.id.clone(),
+                cp: used.into_iter().collect(),
+            },
+            Op::EndTextSection,
+            Op::RestoreGraphicsState,
+        ]);
+
+        for op in pages.iter_mut().flat_map(|page| &mut page.ops) {
+            let Op::WriteCodepointsWithKerning { font, cpk } = op else {
+                continue;
+            };
+            if *font != self.id {
+                continue;
+            }
+            let mut items = Vec::new();
+            for &(kern, gid, _) in cpk.iter() {
+                if kern != 0 {
+                    items.push(DictItem::Int(kern));
+                }
+                let cid = subset_ids.binary_search(&gid).unwrap_or(0) as u16;
+                items.push(DictItem::String {
+                    data: cid.to_be_bytes().to_vec(),
+                    literal: false,
+                });
+            }
+            *op = Op::Unknown {
+                key: "TJ".into(),
+                value: vec![DictItem::Array(items)],
+            };
+        }
+        true
+    }
+}
+
+/// Splits a line into runs of one direction, in the order they appear on the page.
+fn visual_runs(text: &str) -> Vec<(&str, bool)> {
+    let bidi = BidiInfo::new(text, None);
+    let Some(paragraph) = bidi.paragraphs.first() else {
+        return Vec::new();
+    };
+    let (levels, runs) = bidi.visual_runs(paragraph, paragraph.range.clone());
+    runs.into_iter()
+        .map(|run| (&text[run.clone()], levels[run.start].is_rtl()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{Document, Object, content::Content};
+    use printpdf::Point;
+    use rustybuzz::ttf_parser::{self, OutlineBuilder};
+
+    use super::*;
+    use crate::{configs::PageMarginConfig, pdf_doc_util::PdfDocUtil};
+
+    const FONT: &str = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+    // Joining forms and a lam-alef ligature, none of which has a character of its own
+    const RTL_TEXT: &str = "السلام عليكم";
+
+    /// A glyph's drawing commands, equal for the same glyph in the full font and the subset.
+    #[derive(Debug, Default, PartialEq)]
+    struct Outline(Vec<String>);
+
+    impl OutlineBuilder for Outline {
+        fn move_to(&mut self, x: f32, y: f32) {
+            self.0.push(format!("M {} {}", x, y));
+        }
+        fn line_to(&mut self, x: f32, y: f32) {
+            self.0.push(format!("L {} {}", x, y));
+        }
+        fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+            self.0.push(format!("Q {} {} {} {}", x1, y1, x, y));
+        }
+        fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+            self.0
+                .push(format!("C {} {} {} {} {} {}", x1, y1, x2, y2, x, y));
+        }
+        fn close(&mut self) {
+            self.0.push("Z".into());
+        }
+    }
+
+    fn outline(face: &ttf_parser::Face, glyph: u16) -> (Outline, Option<u16>) {
+        let mut outline = Outline::default();
+        face.outline_glyph(GlyphId(glyph), &mut outline);
+        (outline, face.glyph_hor_advance(GlyphId(glyph)))
+    }
+
+    #[test]
+    fn glyph_runs_address_the_shaped_glyphs_in_the_subset() {
+        let Ok(bytes) = std::fs::read(FONT) else {
+            eprintln!("Skipping, {} is not installed", FONT);
+            return;
+        };
+        let face = Face::from_slice(&bytes, 0).unwrap();
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(RTL_TEXT);
+        buffer.guess_segment_properties();
+        buffer.set_direction(Direction::RightToLeft);
+        let shaped: Vec<u16> = rustybuzz::shape(&face, &[], buffer)
+            .glyph_infos()
+            .iter()
+            .map(|info| info.glyph_id as u16)
+            .collect();
+
+        let mut pdf = PdfDocUtil::new(PageMarginConfig::default());
+        pdf.set_font(FONT).unwrap();
+        let ops = pdf.text_line_ops(RTL_TEXT, Pt(12.0), Point::new(Mm(10.0), Mm(10.0)));
+        pdf.add_page_to_document(ops);
+        let saved = Document::load_mem(&pdf.serialize_pdf()).unwrap();
+
+        // The line's run comes first, the invisible run registering the glyphs after it
+        let page = *saved.get_pages().values().next().unwrap();
+        let content = Content::decode(&saved.get_page_content(page).unwrap()).unwrap();
+        let cids: Vec<u16> = content
+            .operations
+            .iter()
+            .filter(|op| op.operator == "TJ")
+            .flat_map(|op| op.operands.iter())
+            .filter_map(|operand| operand.as_array().ok())
+            .flatten()
+            .filter_map(|item| match item {
+                Object::String(data, _) => Some(u16::from_be_bytes([data[0], data[1]])),
+                _ => None,
+            })
+            .take(shaped.len())
+            .collect();
+        assert_eq!(cids.len(), shaped.len());
+
+        let subset = saved
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .find_map(|dict| dict.get(b"FontFile2").ok())
+            .and_then(|font_file| saved.get_object(font_file.as_reference().ok()?).ok())
+            .and_then(|font_file| font_file.as_stream().ok())
+            .map(|stream| {
+                stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone())
+            })
+            .expect("no embedded font");
+        let subset = ttf_parser::Face::parse(&subset, 0).unwrap();
+
+        for (cid, glyph) in cids.into_iter().zip(shaped) {
+            assert_eq!(
+                outline(&subset, cid),
+                outline(&face, glyph),
+                "CID {} is not glyph {}",
+                cid,
+                glyph
+            );
+        }
+    }
+
+    #[test]
+    fn documents_without_glyph_runs_stay_secure() {
+        let Ok(bytes) = std::fs::read(FONT) else {
+            eprintln!("Skipping, {} is not installed", FONT);
+            return;
+        };
+        let font = EmbeddedFont::new(FontId::new(), bytes).unwrap();
+        let mut pages = vec![PdfPage::new(Mm(210.0), Mm(297.0), Vec::new())];
+        assert!(!font.encode_glyph_runs(&mut pages));
+        assert!(pages[0].ops.is_empty());
+    }
+}
@@ -0,0 +1,349 @@
+use std::fmt;
+
+use printpdf::Mm;
+
+use crate::{configs::PageMarginConfig, layout::SlotRect};
+
+// Differences below this are rounding, not layout problems
+const TOLERANCE: Mm = Mm(0.01);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// Not a single card fits on the page
+    NoFit,
+    Overlap,
+    OffPage,
+    /// Inside the strip the printer cannot reach
+    Unprintable,
+    /// The title runs into the cards or the bottom margin
+    TitleCollision,
+    /// Left off the page for lack of room
+    Omitted,
+}
+
+/// One problem found in a layout, with the config fields that would fix it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutIssue {
+    pub kind: IssueKind,
+    pub message: String,
+    pub fields: Vec<&'static str>,
+}
+
+impl LayoutIssue {
+    pub fn new(kind: IssueKind, message: String, fields: &[&'static str]) -> Self {
+        Self {
+            kind,
+            message,
+            fields: fields.to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for LayoutIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.fields.is_empty() {
+            write!(f, " (check {})", self.fields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the slots of one page for cards that overlap each other, leave the page or reach into
+/// the unprintable strips. `side` names the page in the messages, e.g. "back".
+pub fn check_slots(cfg: &PageMarginConfig, slots: &[SlotRect], side: &str) -> Vec<LayoutIssue> {
+    let mut issues = Vec::new();
+    let label = |idx: usize| format!("Card {} on the {}", idx + 1, side);
+    // Duplex corrections move the back and are a likely cause when it goes wrong
+    let (x_offset, y_offset): (&[&'static str], &[&'static str]) = if side == "back" {
+        (&["duplex_offset_x"], &["duplex_offset_y"])
+    } else {
+        (&[], &[])
+    };
+    let with_offset = |fields: &[&'static str], offset: &[&'static str]| -> Vec<&'static str> {
+        fields.iter().chain(offset).copied().collect()
+    };
+
+    // Everything printed for a card: its bleed and its caption band
+    let (below, above) = cfg.caption_bands();
+    let footprints: Vec<SlotRect> = slots
+        .iter()
+        .map(|slot| {
+            let bottom = below.max(cfg.bleed);
+            let top = above.max(cfg.bleed);
+            SlotRect {
+                x: slot.x - cfg.bleed,
+                y: slot.y - bottom,
+                width: slot.width + cfg.bleed * 2.0,
+                height: slot.height + bottom + top,
+                ..*slot
+            }
+        })
+        .collect();
+
+    // The paper edges and the unprintable strips are where the calibrated printer puts things
+    let footprints: Vec<SlotRect> = footprints
+        .iter()
+        .map(|footprint| device_rect(cfg, footprint))
+        .collect();
+    let slots: Vec<SlotRect> = slots.iter().map(|slot| device_rect(cfg, slot)).collect();
+
+    for (idx, first) in footprints.iter().enumerate() {
+        for (other, second) in footprints.iter().enumerate().skip(idx + 1) {
+            let overlap_x = first.right().min(second.right()) - first.x.max(second.x);
+            let overlap_y = first.top().min(second.top()) - first.y.max(second.y);
+            if overlap_x > TOLERANCE && overlap_y > TOLERANCE {
+                issues.push(LayoutIssue::new(
+                    IssueKind::Overlap,
+                    format!(
+                        "{} overlaps card {} by {:.1} x {:.1} mm",
+                        label(idx),
+                        other + 1,
+                        overlap_x.0,
+                        overlap_y.0
+                    ),
+                    &[
+                        "horizontal_spacing",
+                        "vertical_spacing",
+                        "bleed",
+                        "caption.font_size",
+                    ],
+                ));
+            }
+        }
+    }
+
+    for (idx, footprint) in footprints.iter().enumerate() {
+        let past_edges = [
+            ("left", Mm(0.0) - footprint.x),
+            ("right", footprint.right() - cfg.page_width),
+            ("bottom", Mm(0.0) - footprint.y),
+            ("top", footprint.top() - cfg.page_height),
+        ];
+        for (edge, distance) in past_edges {
+            if distance <= TOLERANCE {
+                continue;
+            }
+            let fields = match edge {
+                "left" | "right" => with_offset(
+                    &["margin_left", "horizontal_spacing", "card_width"],
+                    x_offset,
+                ),
+                _ => with_offset(
+                    &[
+                        "margin_top",
+                        "margin_bottom",
+                        "vertical_spacing",
+                        "card_height",
+                    ],
+                    y_offset,
+                ),
+            };
+            issues.push(LayoutIssue::new(
+                IssueKind::OffPage,
+                format!(
+                    "{} sticks out {:.1} mm past the {} page edge",
+                    label(idx),
+                    distance.0,
+                    edge
+                ),
+                &fields,
+            ));
+        }
+    }
+
+    // A bleed lost in the printer's margin is harmless, the trimmed card is not. Cards already
+    // past the page edge were reported above.
+    let unprintable = cfg.unprintable;
+    for (idx, slot) in slots.iter().enumerate() {
+        let into_strips = [
+            ("left", slot.x, unprintable.left, "margin_left", x_offset),
+            (
+                "right",
+                cfg.page_width - slot.right(),
+                unprintable.right,
+                "margin_left",
+                x_offset,
+            ),
+            (
+                "bottom",
+                slot.y,
+                unprintable.bottom,
+                "margin_bottom",
+                y_offset,
+            ),
+            (
+                "top",
+                cfg.page_height - slot.top(),
+                unprintable.top,
+                "margin_top",
+                y_offset,
+            ),
+        ];
+        for (edge, clearance, strip, margin, offset) in into_strips {
+            let distance = strip - clearance;
+            if distance <= TOLERANCE || clearance < Mm(0.0) {
+                continue;
+            }
+            issues.push(LayoutIssue::new(
+                IssueKind::Unprintable,
+                format!(
+                    "{} reaches {:.1} mm into the unprintable strip along the {} edge",
+                    label(idx),
+                    distance.0,
+                    edge
+                ),
+                &with_offset(&[margin, "unprintable"], offset),
+            ));
+        }
+    }
+
+    // A calibrated printer moves everything, which may be what pushed the cards out
+    if !cfg.calibration.is_identity() {
+        for issue in issues
+            .iter_mut()
+            .filter(|issue| issue.kind != IssueKind::Overlap)
+        {
+            issue.fields.push("calibration");
+        }
+    }
+    issues
+}
+
+/// `rect` as the calibrated printer puts it on the paper.
+fn device_rect(cfg: &PageMarginConfig, rect: &SlotRect) -> SlotRect {
+    let (x, y) = cfg.device_point(rect.x, rect.y);
+    let (right, top) = cfg.device_point(rect.right(), rect.top());
+    SlotRect {
+        x,
+        y,
+        width: right - x,
+        height: top - y,
+        ..*rect
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configs::EdgeInsets;
+
+    fn card(x: f32, y: f32) -> SlotRect {
+        SlotRect {
+            x: Mm(x),
+            y: Mm(y),
+            width: Mm(85.6),
+            height: Mm(54.0),
+            rotation: 0,
+        }
+    }
+
+    fn kinds(issues: &[LayoutIssue]) -> Vec<IssueKind> {
+        issues.iter().map(|issue| issue.kind).collect()
+    }
+
+    #[test]
+    fn separate_cards_pass() {
+        let cfg = PageMarginConfig::default();
+        let slots = [card(10.0, 233.0), card(113.6, 233.0), card(10.0, 169.0)];
+        assert_eq!(check_slots(&cfg, &slots, "front"), Vec::new());
+    }
+
+    #[test]
+    fn overlapping_cards() {
+        let cfg = PageMarginConfig::default();
+        let issues = check_slots(&cfg, &[card(10.0, 200.0), card(90.6, 180.0)], "front");
+        assert_eq!(kinds(&issues), [IssueKind::Overlap]);
+        assert_eq!(
+            issues[0].message,
+            "Card 1 on the front overlaps card 2 by 5.0 x 34.0 mm"
+        );
+    }
+
+    #[test]
+    fn touching_cards_do_not_overlap() {
+        let cfg = PageMarginConfig::default();
+        let issues = check_slots(&cfg, &[card(10.0, 200.0), card(95.6, 200.0)], "front");
+        assert_eq!(issues, Vec::new());
+    }
+
+    #[test]
+    fn bleeds_overlap() {
+        let cfg = PageMarginConfig {
+            bleed: Mm(2.0),
+            ..PageMarginConfig::default()
+        };
+        let issues = check_slots(&cfg, &[card(10.0, 200.0), card(97.6, 200.0)], "front");
+        assert_eq!(kinds(&issues), [IssueKind::Overlap]);
+    }
+
+    #[test]
+    fn card_past_the_right_edge() {
+        let cfg = PageMarginConfig::default();
+        let issues = check_slots(&cfg, &[card(130.0, 200.0)], "front");
+        assert_eq!(kinds(&issues), [IssueKind::OffPage]);
+        assert_eq!(
+            issues[0].message,
+            "Card 1 on the front sticks out 5.6 mm past the right page edge"
+        );
+        assert!(issues[0].fields.contains(&"card_width"));
+    }
+
+    #[test]
+    fn back_issues_point_at_the_duplex_offset() {
+        let cfg = PageMarginConfig::default();
+        let issues = check_slots(&cfg, &[card(130.0, 200.0)], "back");
+        assert!(issues[0].fields.contains(&"duplex_offset_x"));
+        assert!(!issues[0].fields.contains(&"duplex_offset_y"));
+    }
+
+    #[test]
+    fn card_in_the_unprintable_strip() {
+        let cfg = PageMarginConfig {
+            unprintable: EdgeInsets {
+                top: Mm(4.0),
+                right: Mm(4.0),
+                bottom: Mm(12.0),
+                left: Mm(4.0),
+            },
+            ..PageMarginConfig::default()
+        };
+        let issues = check_slots(&cfg, &[card(10.0, 5.0)], "front");
+        assert_eq!(kinds(&issues), [IssueKind::Unprintable]);
+        assert_eq!(
+            issues[0].message,
+            "Card 1 on the front reaches 7.0 mm into the unprintable strip along the bottom edge"
+        );
+        assert_eq!(issues[0].fields, ["margin_bottom", "unprintable"]);
+    }
+
+    #[test]
+    fn bleed_in_the_unprintable_strip_is_fine() {
+        let cfg = PageMarginConfig {
+            bleed: Mm(3.0),
+            unprintable: EdgeInsets {
+                top: Mm(0.0),
+                right: Mm(0.0),
+                bottom: Mm(0.0),
+                left: Mm(5.0),
+            },
+            ..PageMarginConfig::default()
+        };
+        assert_eq!(check_slots(&cfg, &[card(6.0, 100.0)], "front"), Vec::new());
+    }
+
+    #[test]
+    fn card_off_the_page_is_not_also_unprintable() {
+        let cfg = PageMarginConfig {
+            unprintable: EdgeInsets {
+                top: Mm(4.0),
+                right: Mm(4.0),
+                bottom: Mm(4.0),
+                left: Mm(4.0),
+            },
+            ..PageMarginConfig::default()
+        };
+        let issues = check_slots(&cfg, &[card(-2.0, 100.0)], "front");
+        assert_eq!(kinds(&issues), [IssueKind::OffPage]);
+    }
+}
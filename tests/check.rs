@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Runs `doc-printable --check` in a directory of its own so no config file is picked up.
+fn check(name: &str, args: &[&str]) -> Output {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_doc-printable"))
+        .arg("--check")
+        .args(args)
+        .current_dir(&dir)
+        .env("XDG_CONFIG_HOME", &dir)
+        .env("HOME", &dir)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn default_layout_passes() {
+    let output = check("default_layout_passes", &[]);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Layout OK"));
+}
+
+#[test]
+fn unprintable_cards_fail() {
+    let output = check("unprintable_cards_fail", &["--unprintable", "15mm"]);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout(&output));
+    assert!(stdout(&output).contains("unprintable strip"));
+}
+
+#[test]
+fn invalid_dimensions_fail() {
+    let output = check("invalid_dimensions_fail", &["--card-width", "500mm"]);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Invalid layout"));
+}
+
+#[test]
+fn overlapping_template_slots_fail() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let template = dir.join("overlapping.toml");
+    std::fs::write(
+        &template,
+        r#"
+page_width = 210.0
+page_height = 297.0
+
+[[pages]]
+slots = [
+    { x = 20.0, y = 220.0, width = 85.6, height = 53.98 },
+    { x = 100.0, y = 220.0, width = 85.6, height = 53.98 },
+]
+"#,
+    )
+    .unwrap();
+    let output = check(
+        "overlapping_template_slots_fail",
+        &["--template", template.to_str().unwrap()],
+    );
+    assert_eq!(output.status.code(), Some(1), "{}", stdout(&output));
+    assert!(stdout(&output).contains("overlaps card 2"));
+}
+
+#[test]
+fn bundled_template_passes() {
+    let template = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/templates/id1-front-back-a4.toml"
+    );
+    let output = check("bundled_template_passes", &["--template", template]);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+}
+
+#[test]
+fn mixed_sheet_left_out_copies_fail() {
+    let output = check(
+        "mixed_sheet_left_out_copies_fail",
+        &["--item", "poster.png:300x400"],
+    );
+    assert_eq!(output.status.code(), Some(1), "{}", stdout(&output));
+    assert!(stdout(&output).contains("1 copies do not fit"));
+}
+
+#[test]
+fn short_edge_duplex_back_title_fits() {
+    let output = check(
+        "short_edge_duplex_back_title_fits",
+        &["--duplex", "short-edge", "-t", "a", "b", "--attest"],
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Layout OK"));
+}
+
+#[test]
+fn short_edge_duplex_back_title_into_the_cards_fails() {
+    let output = check(
+        "short_edge_duplex_back_title_into_the_cards_fails",
+        &[
+            "--duplex",
+            "short-edge",
+            "-t",
+            "a",
+            "b",
+            "--caption",
+            "Copy",
+            "--caption-position",
+            "above",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Title \"b\" on the back"));
+}